@@ -7,10 +7,12 @@
     clippy::unwrap_used,
     clippy::expect_used
 )]
-use duration_human::DurationHumanValidator;
+// dependencies pull in more than one version of some crates, e.g. syn, beyond our control
+#![allow(clippy::multiple_crate_versions)]
+use duration_human::{DurationHuman, DurationHumanValidator};
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use syn::{braced, parse::Parse, parse_macro_input, Ident, Token};
+use syn::{braced, parse::Parse, parse_macro_input, Ident, LitStr, Token};
 
 /// macro
 ///
@@ -104,6 +106,35 @@ pub fn duration_range_value_parse(input: TokenStream) -> TokenStream {
     })
 }
 
+/// macro for a duration that is parsed at compile time, so it can be assigned to a constant
+///
+/// ## Example
+/// ```rust
+/// use clap_duration::duration_human;
+/// use duration_human::DurationHuman;
+///
+/// const LIFETIME: DurationHuman = duration_human!("1h 30min");
+///
+/// assert_eq!(LIFETIME, DurationHuman::parse("90min").unwrap());
+/// ```
+#[proc_macro]
+pub fn duration_human(input: TokenStream) -> TokenStream {
+    let human_readable = parse_macro_input!(input as LitStr);
+
+    match DurationHuman::try_from(human_readable.value().as_str()) {
+        Ok(duration) => {
+            let nanos: u64 = (&duration).into();
+
+            TokenStream::from(quote! {
+                DurationHuman::new(#nanos)
+            })
+        }
+        Err(duration_error) => syn::Error::new(human_readable.span(), duration_error.to_string())
+            .to_compile_error()
+            .into(),
+    }
+}
+
 struct DurationRangeAssignment {
     name: String,
     range: DurationHumanValidator,
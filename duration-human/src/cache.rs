@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use crate::{DurationError, DurationHuman};
+
+/// Parsed durations per input string, the least recently used is evicted when full
+struct ParseCache {
+    entries: HashMap<String, (DurationHuman, u64)>,
+    clock: u64,
+}
+
+impl ParseCache {
+    fn get(&mut self, input: &str) -> Option<DurationHuman> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        self.entries.get_mut(input).map(|(duration, used)| {
+            *used = clock;
+            *duration
+        })
+    }
+
+    fn insert(&mut self, input: &str, duration: DurationHuman) {
+        if self.entries.len() >= DurationHuman::PARSE_CACHE_CAPACITY {
+            let least_recently_used = self
+                .entries
+                .iter()
+                .min_by_key(|(_input, (_duration, used))| *used)
+                .map(|(input, _)| input.clone());
+
+            if let Some(input) = least_recently_used {
+                self.entries.remove(&input);
+            }
+        }
+
+        self.entries
+            .insert(input.to_string(), (duration, self.clock));
+    }
+}
+
+impl DurationHuman {
+    /// At most this many inputs are cached, so the cache holds at most that many strings
+    /// of at most `PARSE_CACHE_MAX_INPUT` bytes each
+    pub const PARSE_CACHE_CAPACITY: usize = 256;
+
+    /// Longer inputs are parsed, but not cached
+    pub const PARSE_CACHE_MAX_INPUT: usize = 64;
+
+    /// Like `parse`, but remembers the durations of recently parsed inputs
+    ///
+    /// Only worth it for inputs that are parsed over and over: a cache hit saves the regex
+    /// matching, but every call takes a lock shared by all threads. Errors are not cached.
+    ///
+    /// ## Errors
+    /// `DurationError` when the parsing fails
+    pub fn parse_cached(human_readable: &str) -> Result<Self, DurationError> {
+        static CACHE: OnceLock<Mutex<ParseCache>> = OnceLock::new();
+
+        if human_readable.len() > Self::PARSE_CACHE_MAX_INPUT {
+            return Self::parse(human_readable);
+        }
+
+        let cache = CACHE.get_or_init(|| {
+            Mutex::new(ParseCache {
+                entries: HashMap::with_capacity(Self::PARSE_CACHE_CAPACITY),
+                clock: 0,
+            })
+        });
+
+        let cached = cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(human_readable);
+
+        cached.map_or_else(
+            || {
+                let duration = Self::parse(human_readable)?;
+                cache
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(human_readable, duration);
+
+                Ok(duration)
+            },
+            Ok,
+        )
+    }
+}
@@ -1,13 +1,216 @@
-use std::fmt::{Debug, Display};
+use std::fmt::{Debug, Display, Write};
 
 use crate::{DurationHuman, DurationHumanValidator};
 
+// the units in u128, like the nano seconds of `total_nanos`
+const MICRO_SEC: u128 = DurationHuman::MICRO_SEC as u128;
+const MILLI_SEC: u128 = DurationHuman::MILLI_SEC as u128;
+const SEC: u128 = DurationHuman::SEC as u128;
+const MINUTE: u128 = DurationHuman::MINUTE as u128;
+const HOUR: u128 = DurationHuman::HOUR as u128;
+const DAY: u128 = DurationHuman::DAY as u128;
+const WEEK: u128 = DurationHuman::WEEK as u128;
+const MONTH: u128 = DurationHuman::MONTH as u128;
+const YEAR: u128 = DurationHuman::YEAR as u128;
+const CENTURY: u128 = DurationHuman::CENTURY as u128;
+
+/// Builder for the pretty format, for options that `{:#}` can not express
+///
+/// ## Example
+/// ```
+/// # use duration_human::{DurationHuman, DurationError};
+/// let duration = DurationHuman::try_from("1 week 1h 30s")?;
+/// assert_eq!(duration.format().max_width(10).to_string(), "1 week 1h".to_string());
+/// assert_eq!(duration.format().max_width(10).with_ellipsis().to_string(), "1 week …".to_string());
+/// assert_eq!(duration.format().with_separator(", ").to_string(), "1 week, 1h, 30s".to_string());
+/// # Ok::<(), DurationError>(())
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct DurationFormat<'a> {
+    duration: DurationHuman,
+    max_width: Option<usize>,
+    ellipsis: bool,
+    separator: &'a str,
+}
+
+impl DurationHuman {
+    /// Pretty format this duration, with more options than `{:#}`
+    #[must_use]
+    pub const fn format(&self) -> DurationFormat<'static> {
+        DurationFormat {
+            duration: *self,
+            max_width: None,
+            ellipsis: false,
+            separator: " ",
+        }
+    }
+
+    /// The canonical compact form, the largest unit in which the duration is a whole number,
+    /// so equal durations always normalize to the same string: "60s" and "1min" both become "1min"
+    #[must_use]
+    pub fn normalize(&self) -> String {
+        self.to_string()
+    }
+
+    /// ISO 8601 notation, with weeks and days in the date portion and hours, minutes and
+    /// (fractional) seconds in the time portion, so "1 day 1h 30min" becomes "P1DT1H30M"
+    ///
+    /// Years and months are never written, as their length depends on the calendar;
+    /// a year is written as 52 weeks and 1.25 days instead
+    #[must_use]
+    pub fn to_iso8601(&self) -> String {
+        let nanos: u128 = self.into();
+        let (weeks, days) = (nanos / WEEK, nanos % WEEK / DAY);
+        let (hours, minutes) = (nanos % DAY / HOUR, nanos % HOUR / MINUTE);
+        let (seconds, fraction) = (nanos % MINUTE / SEC, nanos % SEC);
+
+        let mut iso = String::from("P");
+        // writing to a String does not fail
+        for (value, designator) in [(weeks, 'W'), (days, 'D')] {
+            if value > 0 {
+                let _ = write!(iso, "{value}{designator}");
+            }
+        }
+
+        if hours + minutes + seconds + fraction > 0 {
+            iso.push('T');
+            for (value, designator) in [(hours, 'H'), (minutes, 'M')] {
+                if value > 0 {
+                    let _ = write!(iso, "{value}{designator}");
+                }
+            }
+            if fraction > 0 {
+                let fraction = format!("{fraction:09}");
+                let _ = write!(iso, "{seconds}.{}S", fraction.trim_end_matches('0'));
+            } else if seconds > 0 {
+                let _ = write!(iso, "{seconds}S");
+            }
+        }
+
+        if iso.len() == 1 {
+            iso.push_str("T0S");
+        }
+
+        iso
+    }
+
+    /// Only the largest whole unit, so "3661s" becomes "1h"
+    ///
+    /// This loses precision: all smaller units are dropped, i.e. the duration is floored to that unit
+    #[must_use]
+    pub fn to_coarse(&self) -> String {
+        self.pretty_parts()
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| self.to_string())
+    }
+
+    /// All units with a non-zero value, largest unit first
+    fn pretty_parts(&self) -> Vec<String> {
+        let mut nanos: u128 = self.into();
+        [
+            (CENTURY, " century", " centuries"),
+            (YEAR, " year", " years"),
+            (MONTH, " month", " months"),
+            (WEEK, " week", " weeks"),
+            (DAY, " day", " days"),
+            (HOUR, "h", "h"),
+            (MINUTE, "min", "min"),
+            (SEC, "s", "s"),
+            (MILLI_SEC, "ms", "ms"),
+            (MICRO_SEC, "μs", "μs"),
+            (1, "ns", "ns"),
+        ]
+        .iter()
+        .filter_map(|(part_ms, unit_singular, unit_plural)| {
+            let part = nanos / part_ms;
+            nanos %= part_ms;
+            if part > 0 {
+                Some(format!(
+                    "{}{}",
+                    part,
+                    if part > 1 { unit_plural } else { unit_singular }
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+    }
+}
+
+impl<'a> DurationFormat<'a> {
+    /// Leave out the smaller units that do not fit in `width` characters
+    #[must_use]
+    pub const fn max_width(mut self, width: usize) -> Self {
+        self.max_width = Some(width);
+
+        self
+    }
+
+    /// Append an ellipsis when units had to be left out to fit the maximal width
+    #[must_use]
+    pub const fn with_ellipsis(mut self) -> Self {
+        self.ellipsis = true;
+
+        self
+    }
+
+    /// Join the units with this separator, instead of a single space
+    #[must_use]
+    pub const fn with_separator(mut self, separator: &'a str) -> Self {
+        self.separator = separator;
+
+        self
+    }
+}
+
+impl Display for DurationFormat<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const ELLIPSIS: &str = "…";
+
+        let parts = self.duration.pretty_parts();
+        let separator_width = self.separator.chars().count();
+        let width_of = |count: usize| {
+            parts[..count]
+                .iter()
+                .map(|part| part.chars().count())
+                .sum::<usize>()
+                + count.saturating_sub(1) * separator_width
+        };
+        let max_width = self.max_width.unwrap_or(usize::MAX);
+
+        let mut count = parts.len();
+        while count > 0 && width_of(count) > max_width {
+            count -= 1;
+        }
+
+        let ellipsis = self.ellipsis && count < parts.len();
+        if ellipsis {
+            while count > 0
+                && width_of(count) + separator_width + ELLIPSIS.chars().count() > max_width
+            {
+                count -= 1;
+            }
+        }
+
+        f.write_str(parts[..count].join(self.separator).as_str())?;
+        if ellipsis {
+            if count > 0 {
+                f.write_str(self.separator)?;
+            }
+            f.write_str(ELLIPSIS)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Display for DurationHumanValidator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "must be between {min} and {max}",
-            min = self.min,
-            max = self.max
+            "must be {range}",
+            range = self.describe_range()
         ))
     }
 }
@@ -22,94 +225,71 @@ impl Debug for DurationHumanValidator {
     }
 }
 
+impl Debug for DurationHuman {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DurationHuman")
+            .field("human", &self.to_string())
+            .field("nanos", &self.total_nanos())
+            .finish()
+    }
+}
+
 impl Display for DurationHuman {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut nanos: u64 = self.into();
+        let nanos: u128 = self.into();
         if f.alternate() {
-            let durations: Vec<String> = [
-                (Self::CENTURY, " century", " centuries"),
-                (Self::YEAR, " year", " years"),
-                (Self::MONTH, " month", " months"),
-                (Self::WEEK, " week", " weeks"),
-                (Self::DAY, " day", " days"),
-                (Self::HOUR, "h", "h"),
-                (Self::MINUTE, "min", "min"),
-                (Self::SEC, "s", "s"),
-                (Self::MILLI_SEC, "ms", "ms"),
-                (Self::MICRO_SEC, "μs", "μs"),
-                (1, "ns", "ns"),
-            ]
-            .iter()
-            .filter_map(|(part_ms, unit_singular, unit_plural)| {
-                let part = nanos / part_ms;
-                nanos %= part_ms;
-                if part > 0 {
-                    Some(format!(
-                        "{}{}",
-                        part,
-                        if part > 1 { unit_plural } else { unit_singular }
-                    ))
-                } else {
-                    None
-                }
-            })
-            .collect();
-            f.write_str(durations.join(" ").as_str())
+            f.write_str(self.pretty_parts().join(" ").as_str())
         } else {
             f.write_str(
                 match nanos {
-                    _ if nanos < Self::MICRO_SEC || nanos % Self::MICRO_SEC != 0 => {
+                    _ if nanos < MICRO_SEC || !nanos.is_multiple_of(MICRO_SEC) => {
                         format!("{nanos}ns")
                     }
-                    _ if nanos < Self::MILLI_SEC || nanos % Self::MILLI_SEC != 0 => {
-                        format!("{}μs", nanos / Self::MICRO_SEC)
+                    _ if nanos < MILLI_SEC || !nanos.is_multiple_of(MILLI_SEC) => {
+                        format!("{}μs", nanos / MICRO_SEC)
                     }
-                    _ if nanos < Self::SEC || nanos % Self::SEC != 0 => {
-                        format!("{}ms", nanos / Self::MILLI_SEC)
+                    _ if nanos < SEC || !nanos.is_multiple_of(SEC) => {
+                        format!("{}ms", nanos / MILLI_SEC)
                     }
-                    _ if nanos < Self::MINUTE || nanos % Self::MINUTE != 0 => {
-                        format!("{}s", nanos / Self::SEC)
+                    _ if nanos < MINUTE || !nanos.is_multiple_of(MINUTE) => {
+                        format!("{}s", nanos / SEC)
                     }
-                    _ if nanos < Self::HOUR || nanos % Self::HOUR != 0 => {
-                        format!("{}min", nanos / Self::MINUTE)
+                    _ if nanos < HOUR || !nanos.is_multiple_of(HOUR) => {
+                        format!("{}min", nanos / MINUTE)
                     }
-                    _ if nanos < Self::DAY || nanos % Self::DAY != 0 => {
-                        format!("{}h", nanos / Self::HOUR)
+                    _ if nanos < DAY || !nanos.is_multiple_of(DAY) => {
+                        format!("{}h", nanos / HOUR)
                     }
-                    _ if nanos < Self::WEEK || nanos % Self::WEEK != 0 => {
+                    _ if nanos < WEEK || !nanos.is_multiple_of(WEEK) => {
                         format!(
                             "{} day{}",
-                            nanos / Self::DAY,
-                            if nanos / Self::DAY > 1 { "s" } else { "" }
+                            nanos / DAY,
+                            if nanos / DAY > 1 { "s" } else { "" }
                         )
                     }
-                    _ if nanos < Self::MONTH || nanos % Self::MONTH != 0 => {
+                    _ if nanos < MONTH || !nanos.is_multiple_of(MONTH) => {
                         format!(
                             "{} week{}",
-                            nanos / Self::WEEK,
-                            if nanos / Self::WEEK > 1 { "s" } else { "" }
+                            nanos / WEEK,
+                            if nanos / WEEK > 1 { "s" } else { "" }
                         )
                     }
-                    _ if nanos < Self::YEAR || nanos % Self::YEAR != 0 => format!(
+                    _ if nanos < YEAR || !nanos.is_multiple_of(YEAR) => format!(
                         "{} month{}",
-                        nanos / Self::MONTH,
-                        if nanos / Self::YEAR > 1 { "s" } else { "" }
+                        nanos / MONTH,
+                        if nanos / YEAR > 1 { "s" } else { "" }
                     ),
-                    _ if nanos < Self::CENTURY || nanos % Self::CENTURY != 0 => {
+                    _ if nanos < CENTURY || !nanos.is_multiple_of(CENTURY) => {
                         format!(
                             "{} year{}",
-                            nanos / Self::YEAR,
-                            if nanos / Self::YEAR > 1 { "s" } else { "" }
+                            nanos / YEAR,
+                            if nanos / YEAR > 1 { "s" } else { "" }
                         )
                     }
                     _ => format!(
                         "{} centur{}",
-                        nanos / Self::CENTURY,
-                        if nanos / Self::CENTURY > 1 {
-                            "ies"
-                        } else {
-                            "y"
-                        }
+                        nanos / CENTURY,
+                        if nanos / CENTURY > 1 { "ies" } else { "y" }
                     ),
                 }
                 .as_str(),
@@ -1,27 +1,70 @@
-use std::num::ParseIntError;
+use std::{io, num::ParseIntError, ops::Range};
 
 use thiserror::Error;
 
+use crate::DurationHuman;
+
 #[derive(Error, Debug)]
 pub enum DurationError {
     #[error("Duration must lie between {range}")]
     DurationMustLieBetween { range: String },
 
-    #[error("Duration must be specified as a positive integer, immediately followed by days, h, min, s, ms, μs or ns")]
+    #[error(
+        "Duration must be specified as a positive integer, followed by one of: {units}",
+        units = DurationHuman::UNITS.join(", ")
+    )]
     InvalidSyntax,
 
-    #[error("Invalid duration value")]
+    #[error("Duration must be valid unicode, got {lossy:?}")]
+    NotUnicode { lossy: String },
+
+    #[error("ISO 8601 duration must be specified like P1W2DT3H4M5.6S")]
+    InvalidIso8601Syntax,
+
+    #[error("Clock duration must be specified as h:mm:ss or mm:ss")]
+    InvalidClockSyntax,
+
+    #[error("'{field}' must be less than 60 in a clock duration")]
+    ClockFieldOutOfRange { field: String },
+
+    #[error("Duration can not be negative, {earlier} lies after {later}")]
+    NegativeDuration { earlier: String, later: String },
+
+    #[error("'{part}' is not a valid integer")]
     InvalidValue {
-        #[from]
+        part: String,
+        #[source]
+        source: ParseIntError,
+    },
+
+    /// from a bare `ParseIntError`, which does not tell which part it is about
+    #[error("Duration contains an invalid integer")]
+    InvalidInteger {
+        #[source]
         source: ParseIntError,
     },
 
+    #[error("Duration could not be read")]
+    Io {
+        #[source]
+        source: io::Error,
+    },
+
     #[error("Duration would become too large at {duration}, total should be less than 500 years")]
     IntegerOverflowAt { duration: String },
 
-    #[error("'{sym}' is not supported as a duration symbol")]
+    #[error("'{unit}' is specified more than once")]
+    DuplicateUnit { unit: String },
+
+    #[error(
+        "'{sym}' is not supported as a duration symbol, use one of: {units}",
+        units = DurationHuman::UNITS.join(", ")
+    )]
     UnitMatchAndRegexNotInSync { sym: String },
 
+    #[error("'{range}' is not a range like 1min..1h")]
+    InvalidRangeSyntax { range: String },
+
     #[error("Invalid range: should be {minimal} <=  {maximal}")]
     DurationValidationMinMustBeLessOrEqualMax { minimal: String, maximal: String },
 
@@ -44,3 +87,18 @@ pub enum DurationError {
     #[error("default duration must be 1s or longer")]
     DurationValidationDefaultMustBeMoreThanOneSecond,
 }
+
+/// A `DurationError` with the byte range of the input it is about, e.g. to underline it
+#[derive(Error, Debug)]
+#[error("{source} (at {span:?})")]
+pub struct SpannedDurationError {
+    pub span: Range<usize>,
+    #[source]
+    pub source: DurationError,
+}
+
+impl From<ParseIntError> for DurationError {
+    fn from(source: ParseIntError) -> Self {
+        Self::InvalidInteger { source }
+    }
+}
@@ -7,19 +7,32 @@
     clippy::unwrap_used,
     clippy::expect_used
 )]
+// dependencies pull in more than one version of some crates, e.g. syn, beyond our control
+#![allow(clippy::multiple_crate_versions)]
 
 mod errors;
 pub use errors::*;
 
 mod display;
+pub use display::*;
+
 mod syn;
 
 mod parser;
-pub use parser::*;
+pub use parser::{DurationHuman, DurationParts, RoundMode};
 
 mod validation;
 pub use validation::*;
 
+#[cfg(feature = "chrono")]
+mod timestamp;
+
+#[cfg(feature = "cache")]
+mod cache;
+
+#[cfg(feature = "serde")]
+pub mod serde_pretty;
+
 #[cfg(test)]
 #[allow(clippy::unwrap_in_result, clippy::unwrap_used, clippy::expect_used)]
 mod test;
@@ -1,13 +1,24 @@
-use std::{ops::Add, time::Instant};
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    io::{self, Read},
+    num::ParseIntError,
+    ops::{Add, Range},
+    sync::OnceLock,
+    time::Instant,
+};
 
-use lazy_regex::regex;
+use lazy_regex::{regex, Captures, Regex};
 
-use crate::{DurationError, DurationHumanValidator};
+use crate::{DurationError, DurationHumanValidator, SpannedDurationError};
 
 type StdDuration = std::time::Duration;
 
 /// Define a Duration in human readable form
 ///
+/// Durations are bounded at `u64::MAX` nano seconds (about 584 years), see `DurationHuman::MAX`;
+/// a longer `std::time::Duration` saturates at that bound when converted
+///
 /// ## Examples
 /// ```
 /// # use duration_human::{DurationHuman, DurationError};
@@ -41,11 +52,22 @@ type StdDuration = std::time::Duration;
 /// assert_eq!(format!("{}", duration), "608430s".to_string());
 /// # Ok::<(), DurationError>(())
 /// ```
-#[derive(Clone, PartialEq, Eq, PartialOrd, Copy, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Copy)]
 pub struct DurationHuman {
     inner: StdDuration,
 }
 
+/// How to round a duration to whole units
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundMode {
+    /// towards zero
+    Floor,
+    /// away from zero
+    Ceil,
+    /// to the nearest, with a half rounded up
+    Nearest,
+}
+
 impl DurationHuman {
     pub const MICRO_SEC: u64 = 1_000;
     pub const MILLI_SEC: u64 = 1_000 * Self::MICRO_SEC;
@@ -58,8 +80,15 @@ impl DurationHuman {
     pub const MONTH: u64 = Self::YEAR / 12;
     pub const CENTURY: u64 = 100 * Self::YEAR;
 
+    /// The units that can be parsed, in singular, as listed in `UNIT_TABLE`
+    pub const UNITS: &[&str] = &unit_symbols();
+
     pub const ONE_SECOND: Self = Self::new(Self::SEC);
     pub const ONE_MILLISECOND: Self = Self::new(Self::MILLI_SEC);
+    /// The longest duration, `u64::MAX` nano seconds
+    pub const MAX: Self = Self::new(u64::MAX);
+    /// `from_reader` refuses a stream longer than this, rather than reading it whole
+    pub const MAX_IMPORT_BYTES: u64 = 4096;
 
     #[must_use]
     pub const fn new(nanos: u64) -> Self {
@@ -76,6 +105,465 @@ impl DurationHuman {
         Self::try_from(human_readable)
     }
 
+    /// Read the whole stream, e.g. a file or socket, and parse it as a duration
+    ///
+    /// ## Errors
+    /// `DurationError::Io` when reading fails, the stream is not valid UTF-8 or it is longer
+    /// than `MAX_IMPORT_BYTES`, or any other `DurationError` when the parsing fails
+    pub fn from_reader(reader: impl Read) -> Result<Self, DurationError> {
+        let mut human_readable = String::new();
+        let read = reader
+            .take(Self::MAX_IMPORT_BYTES + 1)
+            .read_to_string(&mut human_readable)
+            .map_err(|source| DurationError::Io { source })?;
+
+        if u64::try_from(read).map_or(true, |read| read > Self::MAX_IMPORT_BYTES) {
+            return Err(DurationError::Io {
+                source: io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("longer than {} bytes", Self::MAX_IMPORT_BYTES),
+                ),
+            });
+        }
+
+        Self::parse(human_readable.trim())
+    }
+
+    /// Parse an optional duration, falling back to `default` when it is missing or blank
+    ///
+    /// ## Errors
+    /// `DurationError` when the input is given, but the parsing fails
+    pub fn parse_or(human_readable: Option<&str>, default: Self) -> Result<Self, DurationError> {
+        human_readable
+            .map_or(Ok(None), Self::parse_opt)
+            .map(|duration| duration.unwrap_or(default))
+    }
+
+    /// Parse a duration that may be left blank, to tell an unset value from an invalid one
+    ///
+    /// ## Errors
+    /// `DurationError` when the input is not blank, but the parsing fails
+    pub fn parse_opt(human_readable: &str) -> Result<Option<Self>, DurationError> {
+        if human_readable.trim().is_empty() {
+            Ok(None)
+        } else {
+            Self::parse(human_readable).map(Some)
+        }
+    }
+
+    /// Parse prose like "half an hour" or "quarter day" as a fraction of one unit,
+    /// or counts with a thousands multiplier like "1k s" or "2M ms",
+    /// next to everything that [`DurationHuman::parse`] accepts
+    ///
+    /// A third is rounded down to whole nano seconds, e.g. a third of a second is 333333333ns
+    ///
+    /// The k (× 1000) or M (× 1000000) multiplier must be followed by a space,
+    /// so "1ms" is still one milli second, "1M s" a million seconds and "1Ms" an error
+    ///
+    /// ## Errors
+    /// `DurationError` when neither the fraction of a unit, nor the duration can be parsed
+    pub fn parse_lenient(prose: &str) -> Result<Self, DurationError> {
+        let prose = regex!(r"\b([0-9]+)\s*([kM])\s+").replace_all(prose, |captures: &Captures| {
+            let zeros = if &captures[2] == "k" { "000" } else { "000000" };
+            format!("{}{zeros} ", &captures[1])
+        });
+
+        let Some(captures) =
+            regex!(r"^\s*(half|quarter|third)\s+(?:an?\s+)?(\S.*?)\s*$").captures(&prose)
+        else {
+            return Self::parse(&prose);
+        };
+
+        let divisor = match &captures[1] {
+            "half" => 2,
+            "quarter" => 4,
+            _ => 3,
+        };
+
+        Self::parse(&format!("1 {}", &captures[2])).map(|unit| Self {
+            inner: unit.inner / divisor,
+        })
+    }
+
+    /// Parse like `TryFrom<&str>`, but report where the input went wrong,
+    /// e.g. for a command line tool to underline it
+    ///
+    /// ## Example
+    /// ```
+    /// # use duration_human::DurationHuman;
+    /// let input = "5h 3 dagen";
+    /// let err = DurationHuman::try_from_spanned(input).unwrap_err();
+    /// assert_eq!(&input[err.span], "3 dagen");
+    /// ```
+    ///
+    /// ## Errors
+    /// `SpannedDurationError` with the span of the first text that is not a duration,
+    /// including a unit that runs on into a longer word like "secondz",
+    /// or of the whole trimmed input when the error is not about such text
+    pub fn try_from_spanned(human_readable: &str) -> Result<Self, SpannedDurationError> {
+        Self::try_from(human_readable).map_err(|source| {
+            let mut end = 0;
+            let mut unsupported = None;
+            for part in parts_splitter().captures_iter(human_readable) {
+                let (Some(whole), Some(unit)) = (part.get(0), part.get(2)) else {
+                    continue;
+                };
+
+                let gap = trimmed_span(human_readable, end..whole.start());
+                if !gap.is_empty() {
+                    unsupported = Some(gap);
+                    break;
+                }
+                // the unit is only the start of the word, like "second" of "secondz"
+                if human_readable[whole.end()..].starts_with(char::is_alphabetic) {
+                    let word_end = human_readable[unit.start()..]
+                        .find(|c: char| !c.is_alphabetic())
+                        .map_or(human_readable.len(), |len| unit.start() + len);
+                    unsupported = Some(unit.start()..word_end);
+                    break;
+                }
+                end = whole.end();
+            }
+
+            let span = unsupported
+                .or_else(|| {
+                    Some(trimmed_span(human_readable, end..human_readable.len()))
+                        .filter(|span| !span.is_empty())
+                })
+                .unwrap_or_else(|| trimmed_span(human_readable, 0..human_readable.len()));
+
+            SpannedDurationError { span, source }
+        })
+    }
+
+    /// Parse the durations at the start of the text, returning the text that follows them
+    ///
+    /// ## Example
+    /// ```
+    /// # use duration_human::{DurationHuman, DurationError};
+    /// let (duration, rest) = DurationHuman::parse_prefix("1h 30min until the next backup")?;
+    /// assert_eq!(format!("{duration:#}"), "1h 30min");
+    /// assert_eq!(rest, "until the next backup");
+    /// # Ok::<(), DurationError>(())
+    /// ```
+    ///
+    /// ## Errors
+    /// `DurationError::InvalidSyntax` when the text does not start with a duration,
+    /// or any other `DurationError` when the parsing fails
+    pub fn parse_prefix(text: &str) -> Result<(Self, &str), DurationError> {
+        let mut end = 0;
+        for part in parts_splitter().find_iter(text) {
+            // a unit must not be the start of a longer word, like "min" in "2 mins"
+            if !text[end..part.start()].trim().is_empty()
+                || text[part.end()..].starts_with(char::is_alphabetic)
+            {
+                break;
+            }
+            end = part.end();
+        }
+
+        if end == 0 {
+            Err(DurationError::InvalidSyntax)
+        } else {
+            Ok((Self::parse(&text[..end])?, text[end..].trim_start()))
+        }
+    }
+
+    /// Create a new duration from ISO 8601 notation, e.g. "P1DT1H30M" or "PT0.5S"
+    ///
+    /// A year counts as 365.25 days and a month as a twelfth of that, like in `parse`
+    ///
+    /// ## Errors
+    /// `DurationError::InvalidIso8601Syntax` when it is not an ISO 8601 duration,
+    /// or `DurationError::IntegerOverflowAt` when it is too large
+    pub fn parse_iso8601(iso: &str) -> Result<Self, DurationError> {
+        let captures = regex!(
+            r"^P(?:([0-9]+)Y)?(?:([0-9]+)M)?(?:([0-9]+)W)?(?:([0-9]+)D)?(?:T(?:([0-9]+)H)?(?:([0-9]+)M)?(?:([0-9]+)(?:[.,]([0-9]{1,9}))?S)?)?$"
+        )
+        .captures(iso)
+        .filter(|_| iso != "P" && !iso.ends_with('T'))
+        .ok_or(DurationError::InvalidIso8601Syntax)?;
+
+        let invalid_value = |part: &str| {
+            let part = part.to_string();
+            move |source| DurationError::InvalidValue { part, source }
+        };
+
+        let fraction = captures.get(8).map_or(Ok(0), |fraction| {
+            format!("{:0<9}", fraction.as_str())
+                .parse::<u64>()
+                .map_err(invalid_value(fraction.as_str()))
+        })?;
+
+        [
+            (1, Self::YEAR),
+            (2, Self::MONTH),
+            (3, Self::WEEK),
+            (4, Self::DAY),
+            (5, Self::HOUR),
+            (6, Self::MINUTE),
+            (7, Self::SEC),
+        ]
+        .iter()
+        .filter_map(|(group, factor)| captures.get(*group).map(|value| (value.as_str(), *factor)))
+        .try_fold(fraction, |nanos_sum, (part, factor)| {
+            let value = part.parse::<u64>().map_err(invalid_value(part))?;
+
+            DurationPart::try_from((part, value, factor))?.add(nanos_sum)
+        })
+        .map(Self::from)
+    }
+
+    /// Like `parse`, but each unit may only be specified once, so "1h 30min 15min" is rejected
+    /// instead of adding up to 1h 45min
+    ///
+    /// ## Errors
+    /// `DurationError::DuplicateUnit` when a unit occurs more than once,
+    /// or any other `DurationError` when the parsing fails
+    pub fn parse_strict(human_readable: &str) -> Result<Self, DurationError> {
+        Self::parse_units(human_readable, true)
+    }
+
+    /// Create a new duration from clock notation, either "h:mm:ss" or "mm:ss"
+    ///
+    /// Only the leftmost field may be 60 or more, so "90:00" is 90 minutes
+    ///
+    /// ## Errors
+    /// `DurationError::InvalidClockSyntax` when there are not two or three fields of digits,
+    /// `DurationError::ClockFieldOutOfRange` when another than the leftmost field is 60 or more
+    pub fn parse_clock(clock: &str) -> Result<Self, DurationError> {
+        let fields: Vec<&str> = clock.trim().split(':').collect();
+        let factors: &[u64] = match fields.len() {
+            3 => &[Self::HOUR, Self::MINUTE, Self::SEC],
+            2 => &[Self::MINUTE, Self::SEC],
+            _ => return Err(DurationError::InvalidClockSyntax),
+        };
+
+        fields
+            .iter()
+            .zip(factors)
+            .enumerate()
+            .try_fold(0, |nanos_sum, (index, (field, factor))| {
+                if field.is_empty() || !field.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(DurationError::InvalidClockSyntax);
+                }
+
+                let value = field
+                    .parse::<u64>()
+                    .map_err(|source| DurationError::InvalidValue {
+                        part: (*field).to_string(),
+                        source,
+                    })?;
+                if index > 0 && value >= 60 {
+                    return Err(DurationError::ClockFieldOutOfRange {
+                        field: (*field).to_string(),
+                    });
+                }
+
+                DurationPart::try_from((*field, value, *factor))?.add(nanos_sum)
+            })
+            .map(Self::from)
+    }
+
+    /// Number of whole weeks in this duration
+    #[must_use]
+    pub const fn as_weeks(&self) -> u64 {
+        self.inner.as_secs() / (Self::WEEK / Self::SEC)
+    }
+
+    /// Number of whole days in this duration
+    #[must_use]
+    pub const fn as_days(&self) -> u64 {
+        self.inner.as_secs() / (Self::DAY / Self::SEC)
+    }
+
+    /// Number of whole hours in this duration
+    #[must_use]
+    pub const fn as_hours(&self) -> u64 {
+        self.inner.as_secs() / (Self::HOUR / Self::SEC)
+    }
+
+    /// Number of whole minutes in this duration
+    #[must_use]
+    pub const fn as_minutes(&self) -> u64 {
+        self.inner.as_secs() / (Self::MINUTE / Self::SEC)
+    }
+
+    /// Number of seconds in this duration, rounded as `mode` says, e.g. for display
+    #[must_use]
+    pub const fn rounded_secs(&self, mode: RoundMode) -> u64 {
+        let secs = self.inner.as_secs();
+        let round_up = match mode {
+            RoundMode::Floor => false,
+            RoundMode::Ceil => self.inner.subsec_nanos() > 0,
+            RoundMode::Nearest => self.inner.subsec_nanos() >= 500_000_000,
+        };
+
+        if round_up {
+            secs + 1
+        } else {
+            secs
+        }
+    }
+
+    /// Strictly longer than `other`
+    #[must_use]
+    pub fn is_longer_than(&self, other: &Self) -> bool {
+        self > other
+    }
+
+    /// Strictly shorter than `other`
+    #[must_use]
+    pub fn is_shorter_than(&self, other: &Self) -> bool {
+        self < other
+    }
+
+    /// At least as long as `other`
+    #[must_use]
+    pub fn is_at_least(&self, other: &Self) -> bool {
+        self >= other
+    }
+
+    /// At most as long as `other`
+    #[must_use]
+    pub fn is_at_most(&self, other: &Self) -> bool {
+        self <= other
+    }
+
+    /// This duration `times` over, e.g. the total of N cycles of an interval
+    ///
+    /// ## Errors
+    /// `DurationError::IntegerOverflowAt` when the total would not fit in u64 nano seconds,
+    /// the limit for parsed durations too
+    pub fn repeat(&self, times: u64) -> Result<Self, DurationError> {
+        u64::try_from(self.total_nanos())
+            .ok()
+            .and_then(|nanos| nanos.checked_mul(times))
+            .map(Self::new)
+            .ok_or_else(|| DurationError::IntegerOverflowAt {
+                duration: format!("{times} × {self}"),
+            })
+    }
+
+    /// Total of all durations, e.g. the steps of a schedule
+    ///
+    /// ## Errors
+    /// `DurationError::IntegerOverflowAt` when the running total would not fit in u64 nano seconds
+    pub fn try_sum(durations: impl IntoIterator<Item = Self>) -> Result<Self, DurationError> {
+        durations
+            .into_iter()
+            .try_fold(Self::new(0), |total, duration| {
+                u64::try_from(total.total_nanos() + duration.total_nanos())
+                    .map(Self::new)
+                    .map_err(|_| DurationError::IntegerOverflowAt {
+                        duration: format!("{total} + {duration}"),
+                    })
+            })
+    }
+
+    /// Endless schedule of `start`, `start × factor`, `start × factor²`, ..., capped at `max`
+    /// and repeating `max` from then on, e.g. for retry backoff
+    pub fn exponential(start: Self, factor: u32, max: Self) -> impl Iterator<Item = Self> {
+        std::iter::successors(Some(start.min(max)), move |previous| {
+            Some(
+                previous
+                    .repeat(u64::from(factor))
+                    .map_or(max, |next| next.min(max)),
+            )
+        })
+    }
+
+    /// The number of frames at this frame rate, rounded to the nearest frame,
+    /// so "2s" at 30 fps is 60 frames and "50ms" at 30 fps is 2 rather than 1.5
+    #[must_use]
+    pub fn as_frames(&self, fps: u32) -> u64 {
+        let sec = u128::from(Self::SEC);
+        let frames = (self.total_nanos() * u128::from(fps) + sec / 2) / sec;
+
+        u64::try_from(frames).unwrap_or(u64::MAX)
+    }
+
+    /// The duration of this many frames at this frame rate, rounded to the nearest nano second;
+    /// at 0 fps a frame lasts forever, thus the longest possible duration
+    #[must_use]
+    pub fn from_frames(frames: u64, fps: u32) -> Self {
+        let fps = u128::from(fps);
+        let nanos = (u128::from(frames) * u128::from(Self::SEC) + fps / 2)
+            .checked_div(fps)
+            .unwrap_or(u128::MAX);
+
+        Self::new(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// This duration, but at least `floor`, e.g. to avoid a zero-length sleep on a computed
+    /// remaining duration
+    #[must_use]
+    pub const fn clamp_min(self, floor: Self) -> Self {
+        if self.inner.as_nanos() < floor.inner.as_nanos() {
+            floor
+        } else {
+            self
+        }
+    }
+
+    /// Halfway between both durations, in either order, without overflowing
+    #[must_use]
+    pub fn midpoint(a: Self, b: Self) -> Self {
+        let (shorter, longer) = if a <= b { (a, b) } else { (b, a) };
+
+        Self {
+            inner: shorter.inner + longer.inner.saturating_sub(shorter.inner) / 2,
+        }
+    }
+
+    /// This duration minus `rhs`, or zero when `rhs` is longer
+    #[must_use]
+    pub const fn saturating_sub(&self, rhs: &Self) -> Self {
+        Self {
+            inner: self.inner.saturating_sub(rhs.inner),
+        }
+    }
+
+    /// The difference between both durations, regardless of which one is longer
+    #[must_use]
+    pub const fn abs_diff(&self, other: &Self) -> Self {
+        let difference = self.saturating_sub(other);
+
+        if difference.inner.is_zero() {
+            other.saturating_sub(self)
+        } else {
+            difference
+        }
+    }
+
+    /// This duration as a `std::time::Duration`, which never exceeds `DurationHuman::MAX`
+    /// as longer durations saturate on construction
+    #[must_use]
+    pub const fn as_std_saturating(&self) -> StdDuration {
+        self.inner
+    }
+
+    /// Total nano seconds in this duration, the same as `u128::from`
+    #[must_use]
+    pub fn total_nanos(&self) -> u128 {
+        u128::from(self)
+    }
+
+    /// Total whole micro seconds in this duration, without truncation
+    #[must_use]
+    pub const fn total_micros(&self) -> u128 {
+        self.inner.as_micros()
+    }
+
+    /// The ratio between this duration and `rhs`, e.g. which fraction of a lifetime has elapsed
+    ///
+    /// Dividing by a zero duration yields `f64::INFINITY`, or `f64::NAN` when both are zero
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // a ratio does not need nano second precision
+    pub fn div_duration_f64(&self, rhs: &Self) -> f64 {
+        self.inner.as_nanos() as f64 / rhs.inner.as_nanos() as f64
+    }
+
     #[must_use]
     pub fn is_in(&self, range: &DurationHumanValidator) -> bool {
         range.contains(self)
@@ -113,8 +601,12 @@ impl Add<Instant> for DurationHuman {
 }
 
 impl From<StdDuration> for DurationHuman {
+    /// Saturates at `DurationHuman::MAX`, rather than exceeding what parsing and the `u64`
+    /// conversion can represent
     fn from(inner: StdDuration) -> Self {
-        Self { inner }
+        Self {
+            inner: inner.min(Self::MAX.inner),
+        }
     }
 }
 
@@ -145,61 +637,238 @@ impl TryFrom<&str> for DurationHuman {
     type Error = DurationError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let matcher = regex!(
-            r"^(?:(\d+)\s*(?:(century|centuries)|(year|month|week|day)(?:s?)|(h|min|s|ms|μs|ns))\s*)*$"
-        );
+        Self::parse_units(value, false)
+    }
+}
+
+impl TryFrom<&OsStr> for DurationHuman {
+    type Error = DurationError;
+
+    /// Parse an OS string, e.g. from an environment variable, which must be valid unicode
+    fn try_from(value: &OsStr) -> Result<Self, Self::Error> {
+        value.to_str().map_or_else(
+            || {
+                Err(DurationError::NotUnicode {
+                    lossy: value.to_string_lossy().into_owned(),
+                })
+            },
+            Self::try_from,
+        )
+    }
+}
 
-        let splitter = regex!(
-            r"(\d+)\s*(?:(century|centuries)|(year|month|week|day)(?:s?)|(h|min|s|ms|μs|ns))"
-        );
+impl DurationHuman {
+    /// Hexadecimal with a `0x` prefix, binary with `0b`, otherwise decimal digits
+    /// which may be grouped, like `1_000` or `1 000 000`
+    fn parse_number(number: &str) -> Result<u64, ParseIntError> {
+        if let Some(hex) = number.strip_prefix("0x") {
+            return u64::from_str_radix(hex, 16);
+        }
+        if let Some(binary) = number.strip_prefix("0b") {
+            return u64::from_str_radix(binary, 2);
+        }
 
-        if !matcher.is_match(value) {
+        number
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>()
+            .parse::<u64>()
+    }
+
+    /// Sum all the value and unit parts; when strict, a unit may only occur once
+    fn parse_units(value: &str, strict: bool) -> Result<Self, DurationError> {
+        // text copied from rich text may hold non-breaking or thin spaces, e.g. "5\u{a0}min"
+        let value = &value.replace(|c: char| c.is_whitespace() && !c.is_ascii(), " ");
+
+        // prose may end the sentence right after the duration, e.g. "5min."; other
+        // punctuation, or more than one mark, is still a syntax error
+        let value = if strict {
+            value.as_str()
+        } else {
+            let trimmed = value.trim_end();
+            trimmed
+                .strip_suffix(|c| c == '.' || c == '!')
+                .unwrap_or(trimmed)
+        };
+
+        // a leading sign is accepted, but durations can not be negative: "-0s" is just zero
+        let value = value.trim_start();
+        let negative = value.starts_with('-');
+        let unsigned = value.strip_prefix(|c| c == '+' || c == '-');
+        if unsigned.is_some_and(|unsigned| unsigned.trim().is_empty()) {
+            return Err(DurationError::InvalidSyntax);
+        }
+        let value = unsigned.unwrap_or(value);
+
+        if !units_matcher().is_match(value) {
             return Err(DurationError::InvalidSyntax);
         }
 
-        splitter
+        let mut units_seen = HashSet::new();
+
+        parts_splitter()
             .captures_iter(value)
             .map(|group| {
-                let value = group[1].parse::<u64>()?;
+                let value = Self::parse_number(&group[1]).map_err(|source| {
+                    DurationError::InvalidValue {
+                        part: group[0].to_string(),
+                        source,
+                    }
+                })?;
+
+                let spelling = &group[2];
+                let Some(unit) = UNIT_TABLE
+                    .iter()
+                    .find(|unit| unit.spellings.contains(&spelling))
+                else {
+                    return Err(DurationError::UnitMatchAndRegexNotInSync {
+                        sym: spelling.to_string(),
+                    });
+                };
+
+                if strict && !units_seen.insert(unit.symbol) {
+                    return Err(DurationError::DuplicateUnit {
+                        unit: unit.symbol.to_string(),
+                    });
+                }
 
                 if value == 0 {
                     Ok(DurationPart::default())
                 } else {
                     let part: &str = group[0].as_ref();
-
-                    #[allow(clippy::unwrap_used)] // somehow the RE has four groups
-                    let unit = group
-                        .get(2)
-                        .or_else(|| group.get(3).or_else(|| group.get(4)))
-                        .unwrap();
-
-                    match unit.as_str() {
-                        "century" | "centuries" => (part, value, Self::CENTURY).try_into(),
-                        "year" => (part, value, Self::YEAR).try_into(),
-                        "month" => (part, value, Self::MONTH).try_into(),
-                        "week" => (part, value, Self::WEEK).try_into(),
-                        "day" => (part, value, Self::DAY).try_into(),
-                        "h" => (part, value, Self::HOUR).try_into(),
-                        "min" => (part, value, Self::MINUTE).try_into(),
-                        "s" => (part, value, Self::SEC).try_into(),
-                        "ms" => (part, value, Self::MILLI_SEC).try_into(),
-                        "μs" => (part, value, Self::MICRO_SEC).try_into(),
-                        "ns" => (part, value, 1).try_into(),
-                        sym => Err(DurationError::UnitMatchAndRegexNotInSync {
-                            sym: sym.to_string(),
-                        }),
-                    }
+                    (part, value, unit.nanos).try_into()
                 }
             })
-            .fold(Ok(0), |nanos_sum, part| {
-                nanos_sum.and_then(|nanos_sum| {
-                    part.and_then(|duration_part| duration_part.add(nanos_sum))
-                })
+            .try_fold(0, |nanos_sum, part| {
+                part.and_then(|duration_part| duration_part.add(nanos_sum))
+            })
+            .and_then(|nanos| {
+                if negative && nanos > 0 {
+                    Err(DurationError::InvalidSyntax)
+                } else {
+                    Ok(Self::from(nanos))
+                }
             })
-            .map(Self::from)
     }
 }
 
+/// The part of the `span` of `text` without leading and trailing whitespace
+fn trimmed_span(text: &str, span: Range<usize>) -> Range<usize> {
+    let part = &text[span.clone()];
+    let start = span.start + (part.len() - part.trim_start().len());
+
+    start..start + part.trim().len()
+}
+
+/// A unit that can be parsed: its symbol, every spelling that is accepted for it and its length
+pub struct Unit {
+    pub symbol: &'static str,
+    pub spellings: &'static [&'static str],
+    pub nanos: u64,
+}
+
+/// The units that can be parsed, from which both the regex and `DurationHuman::UNITS` are derived;
+/// the long spellings come first, so "s" does not match the start of "seconds". Not exported,
+/// the crate root only re-exports the duration types from this module
+pub const UNIT_TABLE: [Unit; 11] = [
+    Unit {
+        symbol: "century",
+        spellings: &["centuries", "century"],
+        nanos: DurationHuman::CENTURY,
+    },
+    Unit {
+        symbol: "year",
+        spellings: &["years", "year"],
+        nanos: DurationHuman::YEAR,
+    },
+    Unit {
+        symbol: "month",
+        spellings: &["months", "month"],
+        nanos: DurationHuman::MONTH,
+    },
+    Unit {
+        symbol: "week",
+        spellings: &["weeks", "week"],
+        nanos: DurationHuman::WEEK,
+    },
+    Unit {
+        symbol: "day",
+        spellings: &["days", "day"],
+        nanos: DurationHuman::DAY,
+    },
+    Unit {
+        symbol: "h",
+        spellings: &["hours", "hour", "h"],
+        nanos: DurationHuman::HOUR,
+    },
+    Unit {
+        symbol: "min",
+        spellings: &["minutes", "minute", "min"],
+        nanos: DurationHuman::MINUTE,
+    },
+    Unit {
+        symbol: "s",
+        spellings: &["seconds", "second", "s"],
+        nanos: DurationHuman::SEC,
+    },
+    Unit {
+        symbol: "ms",
+        spellings: &["ms"],
+        nanos: DurationHuman::MILLI_SEC,
+    },
+    Unit {
+        symbol: "μs",
+        spellings: &["μs"],
+        nanos: DurationHuman::MICRO_SEC,
+    },
+    Unit {
+        symbol: "ns",
+        spellings: &["ns"],
+        nanos: 1,
+    },
+];
+
+const fn unit_symbols() -> [&'static str; UNIT_TABLE.len()] {
+    let mut symbols = [""; UNIT_TABLE.len()];
+    let mut index = 0;
+    while index < UNIT_TABLE.len() {
+        symbols[index] = UNIT_TABLE[index].symbol;
+        index += 1;
+    }
+
+    symbols
+}
+
+/// A value followed by any of the spellings in `UNIT_TABLE`
+fn parts_pattern() -> String {
+    let spellings = UNIT_TABLE
+        .iter()
+        .flat_map(|unit| unit.spellings)
+        .map(|spelling| regex::escape(spelling))
+        .collect::<Vec<_>>()
+        .join("|");
+
+    format!(r"(0x[0-9a-fA-F]+|0b[01]+|[0-9][0-9_]*(?: [0-9]{{3}})*)\s*({spellings})")
+}
+
+/// The whole text consists of value and unit parts
+fn units_matcher() -> &'static Regex {
+    static MATCHER: OnceLock<Regex> = OnceLock::new();
+
+    #[allow(clippy::expect_used)] // the pattern is built from the fixed UNIT_TABLE
+    MATCHER.get_or_init(|| {
+        Regex::new(&format!(r"^(?:{}\s*)*$", parts_pattern())).expect("valid units regex")
+    })
+}
+
+/// The value and unit parts; not anchored, so it also finds them within a longer text
+fn parts_splitter() -> &'static Regex {
+    static SPLITTER: OnceLock<Regex> = OnceLock::new();
+
+    #[allow(clippy::expect_used)] // the pattern is built from the fixed UNIT_TABLE
+    SPLITTER.get_or_init(|| Regex::new(&parts_pattern()).expect("valid units regex"))
+}
+
 impl From<DurationHuman> for clap::builder::OsStr {
     fn from(duration: DurationHuman) -> Self {
         duration.to_string().into()
@@ -208,9 +877,72 @@ impl From<DurationHuman> for clap::builder::OsStr {
 
 impl From<&DurationHuman> for u64 {
     /// convert this duration into nano seconds
-    #[allow(clippy::cast_possible_truncation)] // cast is okay, as u64::MAX as milliseconds is more than 500 million years
+    ///
+    /// Always fits, as durations are bounded at `DurationHuman::MAX`
+    fn from(duration: &DurationHuman) -> Self {
+        Self::try_from(duration.inner.as_nanos()).unwrap_or(Self::MAX)
+    }
+}
+
+impl From<&DurationHuman> for u128 {
+    /// convert this duration into nano seconds
     fn from(duration: &DurationHuman) -> Self {
-        duration.inner.as_nanos() as Self
+        duration.inner.as_nanos()
+    }
+}
+
+/// Named values per unit, for `DurationHuman::from_parts`
+///
+/// ## Example
+/// ```
+/// # use duration_human::{DurationHuman, DurationParts, DurationError};
+/// let duration = DurationHuman::from_parts(DurationParts {
+///     hours: 1,
+///     minutes: 30,
+///     ..Default::default()
+/// })?;
+/// assert_eq!(duration, DurationHuman::try_from("90min")?);
+/// # Ok::<(), DurationError>(())
+/// ```
+#[derive(Default, Clone, Copy, Debug)]
+pub struct DurationParts {
+    pub years: u64,
+    pub months: u64,
+    pub weeks: u64,
+    pub days: u64,
+    pub hours: u64,
+    pub minutes: u64,
+    pub seconds: u64,
+    pub millis: u64,
+    pub micros: u64,
+    pub nanos: u64,
+}
+
+impl DurationHuman {
+    /// Sum the named values per unit
+    ///
+    /// ## Errors
+    /// `DurationError::IntegerOverflowAt` when the total would not fit in u64 nano seconds
+    pub fn from_parts(parts: DurationParts) -> Result<Self, DurationError> {
+        [
+            (parts.years, "year", Self::YEAR),
+            (parts.months, "month", Self::MONTH),
+            (parts.weeks, "week", Self::WEEK),
+            (parts.days, "day", Self::DAY),
+            (parts.hours, "h", Self::HOUR),
+            (parts.minutes, "min", Self::MINUTE),
+            (parts.seconds, "s", Self::SEC),
+            (parts.millis, "ms", Self::MILLI_SEC),
+            (parts.micros, "μs", Self::MICRO_SEC),
+            (parts.nanos, "ns", 1),
+        ]
+        .iter()
+        .try_fold(0, |nanos_sum, (value, unit, factor)| {
+            let part = format!("{value}{unit}");
+
+            DurationPart::try_from((part.as_str(), *value, *factor))?.add(nanos_sum)
+        })
+        .map(Self::from)
     }
 }
 
@@ -251,12 +983,10 @@ impl DurationPart {
     /// ## Errors
     /// if the sum would overflow 2^64, the return is `DurationError::IntegerOverflowAt`
     fn add(&self, rhs: u64) -> Result<u64, DurationError> {
-        if self.nanos > u64::MAX - rhs {
-            return Err(DurationError::IntegerOverflowAt {
-                duration: self.part.to_string(),
-            });
-        }
-
-        Ok(self.nanos + rhs)
+        self.nanos
+            .checked_add(rhs)
+            .ok_or_else(|| DurationError::IntegerOverflowAt {
+                duration: self.part.clone(),
+            })
     }
 }
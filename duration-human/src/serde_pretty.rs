@@ -0,0 +1,35 @@
+//! Serialize a duration in its pretty form, like "1h 30min" rather than "90min",
+//! for human readable configuration files
+//!
+//! ```
+//! # use duration_human::DurationHuman;
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Config {
+//!     #[serde(with = "duration_human::serde_pretty")]
+//!     timeout: DurationHuman,
+//! }
+//! ```
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+use crate::DurationHuman;
+
+/// Serialize as a string in the pretty form, as formatted by `{:#}`
+///
+/// ## Errors
+/// when the serializer fails
+pub fn serialize<S: Serializer>(
+    duration: &DurationHuman,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{duration:#}"))
+}
+
+/// Deserialize from a string in any form that `DurationHuman::try_from` accepts
+///
+/// ## Errors
+/// when the value is not a string, or not a valid duration
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DurationHuman, D::Error> {
+    let human = String::deserialize(deserializer)?;
+
+    DurationHuman::try_from(human.as_str()).map_err(D::Error::custom)
+}
@@ -1,4 +1,7 @@
-use crate::{DurationError, DurationHuman};
+use crate::{
+    DurationError, DurationHuman, DurationHumanValidator, DurationParts, RoundMode,
+    SpannedDurationError,
+};
 
 #[test]
 fn roundtrip() {
@@ -42,8 +45,749 @@ fn max() -> Result<(), DurationError> {
     Ok(())
 }
 
+#[test]
+fn duplicate_units_are_summed() -> Result<(), DurationError> {
+    let duration = DurationHuman::try_from("1h 30min 15min")?;
+    assert_eq!(format!("{duration:#}"), format!("1h 45min"));
+    Ok(())
+}
+
+#[test]
+fn div_duration() -> Result<(), DurationError> {
+    let elapsed = DurationHuman::try_from("30min")?;
+    let lifetime = DurationHuman::try_from("1h")?;
+    assert!((elapsed.div_duration_f64(&lifetime) - 0.5).abs() < f64::EPSILON);
+    assert!((lifetime.div_duration_f64(&elapsed) - 2.0).abs() < f64::EPSILON);
+
+    let zero = DurationHuman::new(0);
+    assert!(elapsed.div_duration_f64(&zero).is_infinite());
+    assert!(zero.div_duration_f64(&zero).is_nan());
+    Ok(())
+}
+
+#[test]
+fn digit_separators() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::try_from("1_000ms")?,
+        DurationHuman::ONE_SECOND
+    );
+    assert_eq!(
+        DurationHuman::try_from("1 000 000ns")?,
+        DurationHuman::ONE_MILLISECOND
+    );
+    assert_eq!(
+        DurationHuman::try_from("1 000 000 ms")?,
+        DurationHuman::try_from("1000s")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("2 weeks 1 000 000 ms")?,
+        DurationHuman::try_from("2 weeks 1000s")?
+    );
+    Ok(())
+}
+
+#[test]
+fn clock() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::parse_clock("1:30:00")?,
+        DurationHuman::try_from("1h 30min")?
+    );
+    assert_eq!(
+        DurationHuman::parse_clock("90:00")?,
+        DurationHuman::try_from("90min")?
+    );
+    assert_eq!(
+        DurationHuman::parse_clock("0:00:05")?,
+        DurationHuman::try_from("5s")?
+    );
+    Ok(())
+}
+
+#[test]
+fn format_max_width() -> Result<(), DurationError> {
+    let duration = DurationHuman::try_from("1 week 1h 30s")?;
+    assert_eq!(duration.format().max_width(10).to_string(), "1 week 1h");
+    assert_eq!(duration.format().max_width(20).to_string(), "1 week 1h 30s");
+
+    let duration = DurationHuman::from(u64::MAX);
+    assert_eq!(
+        duration.format().max_width(20).to_string(),
+        "5 centuries 84 years"
+    );
+    assert_eq!(
+        duration.format().max_width(20).with_ellipsis().to_string(),
+        "5 centuries …"
+    );
+    assert_eq!(duration.format().max_width(10).to_string(), "");
+    assert_eq!(duration.format().to_string(), format!("{duration:#}"));
+    Ok(())
+}
+
+#[test]
+fn format_separator() -> Result<(), DurationError> {
+    let duration = DurationHuman::try_from("1h 30min")?;
+    assert_eq!(
+        duration.format().with_separator(", ").to_string(),
+        "1h, 30min"
+    );
+    assert_eq!(
+        DurationHuman::try_from("1 week 1h 30s")?
+            .format()
+            .with_separator(", ")
+            .max_width(12)
+            .with_ellipsis()
+            .to_string(),
+        "1 week, …"
+    );
+    Ok(())
+}
+
+#[test]
+fn saturates_beyond_u64_nanos() {
+    for beyond_u64 in [
+        std::time::Duration::from_secs(u64::MAX),
+        std::time::Duration::from_nanos(u64::MAX) + std::time::Duration::from_nanos(1),
+    ] {
+        let duration = DurationHuman::from(beyond_u64);
+        assert_eq!(duration, DurationHuman::MAX);
+        assert_eq!(u64::from(&duration), u64::MAX);
+        assert_eq!(
+            duration.as_std_saturating(),
+            std::time::Duration::from_nanos(u64::MAX)
+        );
+    }
+
+    let within = std::time::Duration::from_hours(1);
+    assert_eq!(DurationHuman::from(within).as_std_saturating(), within);
+}
+
+#[test]
+fn intersect_validators() -> Result<(), DurationError> {
+    let global = DurationHumanValidator::try_from(("1min", "5min", "1h"))?;
+    let endpoint = DurationHumanValidator::try_from(("10min", "30min", "2h"))?;
+
+    let intersection = global.intersect(&endpoint)?;
+    assert_eq!(intersection.min, DurationHuman::try_from("10min")?);
+    assert_eq!(intersection.default, DurationHuman::try_from("10min")?);
+    assert_eq!(intersection.max, DurationHuman::try_from("1h")?);
+
+    let intersection = endpoint.intersect(&global)?;
+    assert_eq!(intersection.default, DurationHuman::try_from("30min")?);
+    Ok(())
+}
+
+#[test]
+fn parse_range() -> Result<(), DurationError> {
+    for range in ["1min..1h", "1min..=1h", " 1min .. 1h "] {
+        let validator = DurationHumanValidator::parse_range(range)?;
+        assert_eq!(validator.min, DurationHuman::try_from("1min")?);
+        assert_eq!(validator.max, DurationHuman::try_from("1h")?);
+        assert!(validator.contains(&DurationHuman::try_from("1h")?));
+    }
+    Ok(())
+}
+
+#[test]
+fn validate_std() -> Result<(), DurationError> {
+    let validator = DurationHumanValidator::try_from(("1min", "5min", "1h"))?;
+    validator.validate_std(std::time::Duration::from_mins(1))?;
+    validator.validate_std(std::time::Duration::from_hours(1))?;
+    validator.validate(&DurationHuman::try_from("30min")?)?;
+    Ok(())
+}
+
+#[test]
+fn with_default() -> Result<(), DurationError> {
+    let validator = DurationHumanValidator::try_from(("1min", "5min", "1h"))?
+        .with_default(DurationHuman::try_from("20min")?)?;
+    assert_eq!(
+        validator.default_or_min(),
+        DurationHuman::try_from("20min")?
+    );
+    assert_eq!(validator.min, DurationHuman::try_from("1min")?);
+    assert_eq!(validator.max, DurationHuman::try_from("1h")?);
+
+    let validator = DurationHumanValidator {
+        default: DurationHuman::try_from("10s")?,
+        ..validator
+    };
+    assert_eq!(validator.default_or_min(), DurationHuman::try_from("1min")?);
+    Ok(())
+}
+
+#[test]
+fn coarse() -> Result<(), DurationError> {
+    for (duration, coarse) in [
+        ("3661s", "1h"),
+        ("90s", "1min"),
+        ("1 week 1h", "1 week"),
+        ("3 days 23h 59min", "3 days"),
+        ("999ms", "999ms"),
+        ("1500μs", "1ms"),
+        ("0s", "0ns"),
+    ] {
+        assert_eq!(DurationHuman::try_from(duration)?.to_coarse(), coarse);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_pretty() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Config {
+        #[serde(with = "crate::serde_pretty")]
+        timeout: DurationHuman,
+    }
+
+    let config = Config {
+        timeout: DurationHuman::try_from("90min")?,
+    };
+    let json = serde_json::to_string(&config)?;
+    assert_eq!(json, r#"{"timeout":"1h 30min"}"#);
+    assert_eq!(serde_json::from_str::<Config>(&json)?, config);
+
+    assert!(serde_json::from_str::<Config>(r#"{"timeout":"90 mins"}"#).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn parse_cached() -> Result<(), DurationError> {
+    let uncached = DurationHuman::parse("1h 30min")?;
+    for _ in 0..3 {
+        assert_eq!(DurationHuman::parse_cached("1h 30min")?, uncached);
+    }
+
+    // beyond the capacity, the least recently used inputs are evicted, not the results changed
+    let capacity = u64::try_from(DurationHuman::PARSE_CACHE_CAPACITY).unwrap();
+    for seconds in 0..=capacity {
+        assert_eq!(
+            DurationHuman::parse_cached(&format!("{seconds}s"))?,
+            DurationHuman::new(seconds * DurationHuman::SEC)
+        );
+    }
+    assert_eq!(DurationHuman::parse_cached("1h 30min")?, uncached);
+    assert!(DurationHuman::parse_cached("1 fortnight").is_err());
+    Ok(())
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn until_next_time_of_day() -> Result<(), DurationError> {
+    let now = chrono::DateTime::parse_from_rfc3339("2025-01-01T10:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let at = |hour, minute| chrono::NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+
+    assert_eq!(
+        DurationHuman::until_next(at(12, 30), now),
+        DurationHuman::try_from("2h 30min")?
+    );
+    assert_eq!(
+        DurationHuman::until_next(at(9, 0), now),
+        DurationHuman::try_from("23h")?
+    );
+    assert_eq!(
+        DurationHuman::until_next(at(10, 0), now),
+        DurationHuman::new(0)
+    );
+    Ok(())
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn between_timestamps() -> Result<(), DurationError> {
+    let earlier = chrono::DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let later = earlier + chrono::Duration::minutes(90);
+
+    assert_eq!(
+        DurationHuman::between(earlier, later)?,
+        DurationHuman::try_from("1h 30min")?
+    );
+    assert!(matches!(
+        DurationHuman::between(later, earlier),
+        Err(DurationError::NegativeDuration { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn whole_units() -> Result<(), DurationError> {
+    assert_eq!(DurationHuman::try_from("10day")?.as_weeks(), 1);
+    assert_eq!(DurationHuman::try_from("10day")?.as_days(), 10);
+    assert_eq!(DurationHuman::try_from("90min")?.as_hours(), 1);
+    assert_eq!(DurationHuman::try_from("90min")?.as_minutes(), 90);
+    assert_eq!(DurationHuman::try_from("59s")?.as_minutes(), 0);
+    Ok(())
+}
+
+#[test]
+fn try_from_spanned() -> Result<(), SpannedDurationError> {
+    assert_eq!(
+        DurationHuman::try_from_spanned("1h 30min")?,
+        DurationHuman::new(90 * DurationHuman::MINUTE)
+    );
+    Ok(())
+}
+
+#[test]
+fn trailing_period() -> Result<(), DurationError> {
+    for prose in ["5min.", "5min!", "5 min .", "5min. "] {
+        assert_eq!(
+            DurationHuman::try_from(prose)?,
+            DurationHuman::try_from("5min")?,
+            "{prose}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn signed_zero() -> Result<(), DurationError> {
+    for zero in ["+0s", "-0ns", "-0h 0min"] {
+        assert_eq!(
+            DurationHuman::try_from(zero)?,
+            DurationHuman::new(0),
+            "{zero}"
+        );
+    }
+    assert_eq!(
+        DurationHuman::try_from("+5min")?,
+        DurationHuman::try_from("5min")?
+    );
+    Ok(())
+}
+
+#[test]
+fn unicode_whitespace() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::try_from("5\u{a0}min")?,
+        DurationHuman::try_from("5min")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("1\u{202f}000\u{2009}ms")?,
+        DurationHuman::try_from("1s")?
+    );
+    Ok(())
+}
+
+#[test]
+fn debug() -> Result<(), DurationError> {
+    assert_eq!(
+        format!("{:?}", DurationHuman::try_from("90min")?),
+        r#"DurationHuman { human: "90min", nanos: 5400000000000 }"#
+    );
+    Ok(())
+}
+
+#[test]
+fn total_nanos_and_micros() -> Result<(), DurationError> {
+    let duration = DurationHuman::try_from("5 centuries")?;
+    assert_eq!(
+        duration.total_nanos(),
+        5 * u128::from(DurationHuman::CENTURY)
+    );
+    assert_eq!(
+        duration.total_micros(),
+        5 * u128::from(DurationHuman::CENTURY / DurationHuman::MICRO_SEC)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn parse_or_default() -> Result<(), DurationError> {
+    let default = DurationHuman::try_from("1h")?;
+    assert_eq!(DurationHuman::parse_or(None, default)?, default);
+    assert_eq!(DurationHuman::parse_or(Some(""), default)?, default);
+    assert_eq!(
+        DurationHuman::parse_or(Some("5min"), default)?,
+        DurationHuman::try_from("5min")?
+    );
+    assert!(DurationHuman::parse_or(Some("5 mins"), default).is_err());
+    Ok(())
+}
+
+#[test]
+fn from_reader() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::from_reader(std::io::Cursor::new(b"5min\n".as_slice()))?,
+        DurationHuman::try_from("5min")?
+    );
+    Ok(())
+}
+
+#[test]
+fn parse_opt() -> Result<(), DurationError> {
+    assert_eq!(DurationHuman::parse_opt("")?, None);
+    assert_eq!(DurationHuman::parse_opt(" \t")?, None);
+    assert_eq!(
+        DurationHuman::parse_opt("5min")?,
+        Some(DurationHuman::try_from("5min")?)
+    );
+    assert!(matches!(
+        DurationHuman::parse_opt("bad"),
+        Err(DurationError::InvalidSyntax)
+    ));
+    Ok(())
+}
+
+#[test]
+fn long_spellings() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::try_from("5 seconds")?,
+        DurationHuman::try_from("5s")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("2 hours")?,
+        DurationHuman::try_from("2h")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("1 hour 1 minute 1 second")?,
+        DurationHuman::try_from("1h 1min 1s")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("3 minutes 2ms")?,
+        DurationHuman::try_from("3min 2ms")?
+    );
+    assert!(matches!(
+        DurationHuman::parse_strict("1 hour 1h"),
+        Err(DurationError::DuplicateUnit { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn from_os_str() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::try_from(std::ffi::OsStr::new("5min"))?,
+        DurationHuman::try_from("5min")?
+    );
+    Ok(())
+}
+
+#[test]
+fn parse_prefix() -> Result<(), DurationError> {
+    let (duration, rest) = DurationHuman::parse_prefix("5min rest of string")?;
+    assert_eq!(duration, DurationHuman::try_from("5min")?);
+    assert_eq!(rest, "rest of string");
+
+    let (duration, rest) = DurationHuman::parse_prefix("1h30min")?;
+    assert_eq!(duration, DurationHuman::try_from("90min")?);
+    assert_eq!(rest, "");
+
+    let (duration, rest) = DurationHuman::parse_prefix("1 day 2 mins")?;
+    assert_eq!(duration, DurationHuman::try_from("1day")?);
+    assert_eq!(rest, "2 mins");
+    Ok(())
+}
+
+#[test]
+fn normalize() -> Result<(), DurationError> {
+    assert_eq!(DurationHuman::try_from("60s")?.normalize(), "1min");
+    assert_eq!(DurationHuman::try_from("1min")?.normalize(), "1min");
+    assert_eq!(DurationHuman::try_from("1h 30min")?.normalize(), "90min");
+    assert_eq!(DurationHuman::try_from("5400s")?.normalize(), "90min");
+    Ok(())
+}
+
+#[test]
+fn from_parts() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::from_parts(DurationParts {
+            hours: 1,
+            minutes: 30,
+            ..Default::default()
+        })?,
+        DurationHuman::try_from("1h 30min")?
+    );
+    assert_eq!(
+        DurationHuman::from_parts(DurationParts {
+            weeks: 1,
+            days: 2,
+            seconds: 5,
+            millis: 250,
+            ..Default::default()
+        })?,
+        DurationHuman::try_from("1 week 2 days 5s 250ms")?
+    );
+    assert_eq!(
+        DurationHuman::from_parts(DurationParts::default())?,
+        DurationHuman::new(0)
+    );
+    assert!(matches!(
+        DurationHuman::from_parts(DurationParts {
+            years: 500,
+            months: 12 * 100,
+            ..Default::default()
+        }),
+        Err(DurationError::IntegerOverflowAt { duration }) if duration == "1200month"
+    ));
+    Ok(())
+}
+
+#[test]
+fn iso8601_roundtrip() -> Result<(), DurationError> {
+    for (duration, iso) in [
+        ("1h 30min", "PT1H30M"),
+        ("1 week 2 days 1h 500ms", "P1W2DT1H0.5S"),
+        ("3 days 45s 1ns", "P3DT45.000000001S"),
+        ("0s", "PT0S"),
+    ] {
+        let duration = DurationHuman::try_from(duration)?;
+        assert_eq!(duration.to_iso8601(), iso);
+        assert_eq!(DurationHuman::parse_iso8601(iso)?, duration);
+    }
+
+    assert_eq!(
+        DurationHuman::parse_iso8601("P1Y2M")?,
+        DurationHuman::try_from("1 year 2 months")?
+    );
+    assert_eq!(
+        DurationHuman::parse_iso8601("PT1,25S")?,
+        DurationHuman::try_from("1s 250ms")?
+    );
+    Ok(())
+}
+
+#[test]
+fn parse_lenient() -> Result<(), DurationError> {
+    for (prose, duration) in [
+        ("half an hour", "30min"),
+        ("quarter day", "6h"),
+        ("third hour", "20min"),
+        ("third second", "333ms 333μs 333ns"),
+        ("half a minute", "30s"),
+        ("90min", "90min"),
+        ("1k s", "1000s"),
+        ("2M ms", "2000s"),
+        ("1ms", "1ms"),
+        ("1M s", "1000000s"),
+    ] {
+        assert_eq!(
+            DurationHuman::parse_lenient(prose)?,
+            DurationHuman::try_from(duration)?,
+            "{prose}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn frames() -> Result<(), DurationError> {
+    let two_seconds = DurationHuman::try_from("2s")?;
+    assert_eq!(two_seconds.as_frames(30), 60);
+    assert_eq!(DurationHuman::from_frames(60, 30), two_seconds);
+    assert_eq!(DurationHuman::try_from("50ms")?.as_frames(30), 2);
+
+    for fps in [24, 30, 60] {
+        for frames in [0, 1, 7, 1_000, 86_400 * 60] {
+            assert_eq!(
+                DurationHuman::from_frames(frames, fps).as_frames(fps),
+                frames,
+                "{frames} frames at {fps} fps"
+            );
+        }
+        assert_eq!(two_seconds.as_frames(fps), 2 * u64::from(fps));
+    }
+
+    assert_eq!(two_seconds.as_frames(0), 0);
+    assert_eq!(
+        DurationHuman::from_frames(1, 0),
+        DurationHuman::new(u64::MAX)
+    );
+    Ok(())
+}
+
+#[test]
+fn clamp_min() -> Result<(), DurationError> {
+    let floor = DurationHuman::new(1);
+    assert_eq!(DurationHuman::new(0).clamp_min(floor), floor);
+    assert_eq!(
+        DurationHuman::try_from("5min")?.clamp_min(floor),
+        DurationHuman::try_from("5min")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("1s")?.clamp_min(DurationHuman::try_from("1min")?),
+        DurationHuman::try_from("1min")?
+    );
+    Ok(())
+}
+
+#[test]
+fn midpoint() -> Result<(), DurationError> {
+    let minute = DurationHuman::try_from("1min")?;
+    let hour = DurationHuman::try_from("1h")?;
+    let halfway = DurationHuman::try_from("30min 30s")?;
+
+    assert_eq!(DurationHuman::midpoint(minute, hour), halfway);
+    assert_eq!(DurationHuman::midpoint(hour, minute), halfway);
+    assert_eq!(DurationHuman::midpoint(hour, hour), hour);
+
+    // the sum of both would overflow u64 nano seconds
+    assert_eq!(
+        DurationHuman::midpoint(
+            DurationHuman::new(u64::MAX),
+            DurationHuman::new(u64::MAX - 2)
+        ),
+        DurationHuman::new(u64::MAX - 1)
+    );
+
+    assert_eq!(
+        DurationHumanValidator::try_from(("1min", "1h"))?.default,
+        halfway
+    );
+    Ok(())
+}
+
+#[test]
+fn rounded_secs() -> Result<(), DurationError> {
+    let duration = DurationHuman::try_from("1500ms")?;
+    assert_eq!(duration.rounded_secs(RoundMode::Floor), 1);
+    assert_eq!(duration.rounded_secs(RoundMode::Ceil), 2);
+    assert_eq!(duration.rounded_secs(RoundMode::Nearest), 2);
+
+    let duration = DurationHuman::try_from("1499ms")?;
+    assert_eq!(duration.rounded_secs(RoundMode::Nearest), 1);
+
+    let duration = DurationHuman::try_from("2s")?;
+    for mode in [RoundMode::Floor, RoundMode::Ceil, RoundMode::Nearest] {
+        assert_eq!(duration.rounded_secs(mode), 2, "{mode:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn repeat() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::try_from("90s")?.repeat(4)?,
+        DurationHuman::try_from("6min")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("1h")?.repeat(0)?,
+        DurationHuman::new(0)
+    );
+    Ok(())
+}
+
+#[test]
+fn try_sum() -> Result<(), DurationError> {
+    let steps = ["90s", "30s", "1h"]
+        .into_iter()
+        .map(DurationHuman::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(
+        DurationHuman::try_sum(steps)?,
+        DurationHuman::try_from("62min")?
+    );
+    assert_eq!(DurationHuman::try_sum([])?, DurationHuman::new(0));
+    Ok(())
+}
+
+#[test]
+fn exponential() -> Result<(), DurationError> {
+    let backoff = DurationHuman::exponential(
+        DurationHuman::try_from("1s")?,
+        2,
+        DurationHuman::try_from("10s")?,
+    )
+    .take(7)
+    .map(|duration| duration.to_string())
+    .collect::<Vec<_>>();
+    assert_eq!(backoff, ["1s", "2s", "4s", "8s", "10s", "10s", "10s"]);
+
+    // multiplying would overflow, which saturates at the max as well
+    let max = DurationHuman::new(u64::MAX);
+    let mut huge = DurationHuman::exponential(DurationHuman::new(u64::MAX / 2 + 1), 3, max);
+    assert_eq!(huge.nth(1), Some(max));
+    Ok(())
+}
+
+#[test]
+fn differences() -> Result<(), DurationError> {
+    let hour = DurationHuman::try_from("1h")?;
+    let forty_minutes = DurationHuman::try_from("40min")?;
+    let twenty_minutes = DurationHuman::try_from("20min")?;
+
+    assert_eq!(hour.abs_diff(&forty_minutes), twenty_minutes);
+    assert_eq!(forty_minutes.abs_diff(&hour), twenty_minutes);
+    assert_eq!(hour.abs_diff(&hour), DurationHuman::new(0));
+
+    assert_eq!(hour.saturating_sub(&forty_minutes), twenty_minutes);
+    assert_eq!(forty_minutes.saturating_sub(&hour), DurationHuman::new(0));
+    Ok(())
+}
+
+#[test]
+fn compare() -> Result<(), DurationError> {
+    let hour = DurationHuman::try_from("1h")?;
+    let sixty_minutes = DurationHuman::try_from("60min")?;
+    let day = DurationHuman::try_from("1day")?;
+
+    assert!(!hour.is_longer_than(&sixty_minutes));
+    assert!(!hour.is_shorter_than(&sixty_minutes));
+    assert!(hour.is_at_least(&sixty_minutes));
+    assert!(hour.is_at_most(&sixty_minutes));
+
+    assert!(day.is_longer_than(&hour));
+    assert!(day.is_at_least(&hour));
+    assert!(!day.is_shorter_than(&hour));
+    assert!(!day.is_at_most(&hour));
+
+    assert!(hour.is_shorter_than(&day));
+    assert!(hour.is_at_most(&day));
+    assert!(!hour.is_longer_than(&day));
+    assert!(!hour.is_at_least(&day));
+    Ok(())
+}
+
+#[test]
+fn radix_prefix() -> Result<(), DurationError> {
+    assert_eq!(
+        DurationHuman::try_from("0x3e8ms")?,
+        DurationHuman::try_from("1s")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("0x1dcd6500ns")?,
+        DurationHuman::try_from("500ms")?
+    );
+    assert_eq!(
+        DurationHuman::try_from("0b101min 0xAs")?,
+        DurationHuman::try_from("5min 10s")?
+    );
+    Ok(())
+}
+
+#[test]
+fn describe_range() -> Result<(), DurationError> {
+    let validator = DurationHumanValidator::try_from(("10min", "2h", "60day"))?;
+    assert_eq!(validator.describe_range(), "between 10min and 60 days");
+    assert_eq!(validator.to_string(), "must be between 10min and 60 days");
+    Ok(())
+}
+
+#[test]
+fn space_before_unit() -> Result<(), DurationError> {
+    let five_minutes = DurationHuman::try_from("5min")?;
+    assert_eq!(DurationHuman::try_from("5 min")?, five_minutes);
+    assert_eq!(DurationHuman::parse_strict("5 min")?, five_minutes);
+    assert_eq!(
+        DurationHumanValidator::new(
+            DurationHuman::SEC,
+            DurationHuman::MINUTE,
+            DurationHuman::HOUR
+        )
+        .parse_and_validate("5 min")?,
+        five_minutes
+    );
+    Ok(())
+}
+
 mod errors {
-    use crate::{DurationError, DurationHuman};
+    use crate::{DurationError, DurationHuman, DurationHumanValidator};
 
     #[test]
     fn overflow() {
@@ -85,4 +829,343 @@ mod errors {
             }
         }
     }
+
+    #[test]
+    fn duplicate_unit_in_strict_mode() {
+        let result = DurationHuman::parse_strict("1h 30min 15min");
+        assert!(
+            matches!(result, Err(DurationError::DuplicateUnit { ref unit }) if unit == "min"),
+            "Expected a duplicate unit error, got {result:?}"
+        );
+
+        let result = DurationHuman::parse_strict("1 century 2 centuries");
+        assert!(matches!(result, Err(DurationError::DuplicateUnit { .. })));
+    }
+
+    #[test]
+    fn clock_field_out_of_range() {
+        let result = DurationHuman::parse_clock("1:70:00");
+        assert!(
+            matches!(result, Err(DurationError::ClockFieldOutOfRange { ref field }) if field == "70"),
+            "Expected an out of range error, got {result:?}"
+        );
+    }
+
+    #[test]
+    fn spanned_syntax_error() {
+        for (input, bad) in [
+            ("5h 3 dagen", "3 dagen"),
+            ("5h foo 30min", "foo"),
+            ("  eleven  ", "eleven"),
+            (" 600 centuries ", "600 centuries"),
+            ("3 secondz", "secondz"),
+            ("5h 3 secondz", "secondz"),
+            ("1h 2 minsx 5s", "minsx"),
+        ] {
+            let err = DurationHuman::try_from_spanned(input).unwrap_err();
+            assert_eq!(&input[err.span], bad, "{input}");
+        }
+
+        let err = DurationHuman::try_from_spanned("5h 3 dagen").unwrap_err();
+        assert_eq!(err.span, 3..10);
+        assert!(matches!(err.source, DurationError::InvalidSyntax));
+    }
+
+    #[test]
+    fn punctuation_syntax_error() {
+        for prose in ["5min..", "5min?", "5.min", "1h, 5min", "-."] {
+            assert!(
+                matches!(
+                    DurationHuman::try_from(prose),
+                    Err(DurationError::InvalidSyntax)
+                ),
+                "{prose}"
+            );
+        }
+        assert!(matches!(
+            DurationHuman::parse_strict("5min."),
+            Err(DurationError::InvalidSyntax)
+        ));
+    }
+
+    #[test]
+    fn clock_syntax_error() {
+        for clock in ["1h", "1:2:3:4", "1::00", "-1:00", "1:3O"] {
+            assert!(
+                matches!(
+                    DurationHuman::parse_clock(clock),
+                    Err(DurationError::InvalidClockSyntax)
+                ),
+                "Expected a syntax error for '{clock}'"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_value_names_the_part() {
+        let result = DurationHuman::try_from("1h 99999999999999999999s");
+        assert!(matches!(result, Err(DurationError::InvalidValue { .. })));
+        assert!(result.is_err_and(|err| err
+            .to_string()
+            .contains("'99999999999999999999s' is not a valid integer")));
+    }
+
+    #[test]
+    fn from_parse_int_error() {
+        let err = DurationError::from("5x".parse::<u64>().unwrap_err());
+        assert!(matches!(err, DurationError::InvalidInteger { .. }));
+        assert!(!err.to_string().contains("''"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn os_str_not_unicode() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        assert!(matches!(
+            DurationHuman::try_from(OsStr::from_bytes(b"5m\x80in")),
+            Err(DurationError::NotUnicode { .. })
+        ));
+    }
+
+    #[test]
+    fn message_lists_units() {
+        let message = DurationError::InvalidSyntax.to_string();
+        assert!(message.ends_with("century, year, month, week, day, h, min, s, ms, μs, ns"));
+
+        let message = DurationError::UnitMatchAndRegexNotInSync {
+            sym: String::from("fortnight"),
+        }
+        .to_string();
+        assert!(message.starts_with("'fortnight' is not supported"));
+        assert!(message.ends_with(&DurationHuman::UNITS.join(", ")));
+    }
+
+    #[test]
+    fn every_unit_in_the_table_parses() -> Result<(), DurationError> {
+        assert_eq!(DurationHuman::UNITS.len(), crate::parser::UNIT_TABLE.len());
+
+        for unit in &crate::parser::UNIT_TABLE {
+            assert!(
+                DurationHuman::UNITS.contains(&unit.symbol),
+                "{} is not listed",
+                unit.symbol
+            );
+            for spelling in unit.spellings {
+                assert_eq!(
+                    DurationHuman::try_from(format!("2{spelling}").as_str())?,
+                    DurationHuman::new(2 * unit.nanos),
+                    "{spelling} does not parse as {}",
+                    unit.symbol
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn no_leading_duration() {
+        for text in ["rest of string 5min", "5mins", ""] {
+            assert!(
+                matches!(
+                    DurationHuman::parse_prefix(text),
+                    Err(DurationError::InvalidSyntax)
+                ),
+                "{text} does not start with a duration"
+            );
+        }
+    }
+
+    #[test]
+    fn overflow_in_the_sum() {
+        // each part fits, but the third one pushes the total over
+        for (duration, overflow_at) in [
+            ("300 years 200 years 100 years", "100 years"),
+            ("2 centuries 2 centuries 2 centuries", "2 centuries"),
+        ] {
+            assert!(
+                matches!(
+                    DurationHuman::try_from(duration),
+                    Err(DurationError::IntegerOverflowAt { duration }) if duration == overflow_at
+                ),
+                "{duration} should overflow at {overflow_at}"
+            );
+        }
+        assert!(matches!(
+            DurationHuman::parse_clock("5124095:35:00"),
+            Err(DurationError::IntegerOverflowAt { .. })
+        ));
+    }
+
+    #[test]
+    fn repeat_overflow() -> Result<(), DurationError> {
+        let century = DurationHuman::try_from("1 century")?;
+        assert!(matches!(
+            century.repeat(6),
+            Err(DurationError::IntegerOverflowAt { duration }) if duration == format!("6 × {century}")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn try_sum_overflow() -> Result<(), DurationError> {
+        let century = DurationHuman::try_from("1 century")?;
+        assert!(matches!(
+            DurationHuman::try_sum([century; 6]),
+            Err(DurationError::IntegerOverflowAt { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn iso8601_syntax_error() {
+        for iso in [
+            "P",
+            "PT",
+            "P1DT",
+            "1H",
+            "PT1H1D",
+            "P1.5D",
+            "PT1.1234567891S",
+        ] {
+            assert!(
+                matches!(
+                    DurationHuman::parse_iso8601(iso),
+                    Err(DurationError::InvalidIso8601Syntax)
+                ),
+                "{iso} is not an ISO 8601 duration"
+            );
+        }
+    }
+
+    #[test]
+    fn misspelled_long_unit() {
+        assert!(matches!(
+            DurationHuman::try_from("5 secondz"),
+            Err(DurationError::InvalidSyntax)
+        ));
+    }
+
+    #[test]
+    fn malformed_hex() {
+        for input in ["0x1gms", "0xms", "0b12s"] {
+            assert!(
+                matches!(
+                    DurationHuman::try_from(input),
+                    Err(DurationError::InvalidSyntax)
+                ),
+                "{input} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn non_ascii_digits() {
+        for input in ["1٣ms", "٣s", "1 ٣٣٣min", "P٣D"] {
+            assert!(
+                DurationHuman::try_from(input).is_err()
+                    && DurationHuman::parse_iso8601(input).is_err(),
+                "{input} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn disjoint_validators() -> Result<(), DurationError> {
+        let short = DurationHumanValidator::try_from(("1s", "1min"))?;
+        let long = DurationHumanValidator::try_from(("1h", "2h"))?;
+        assert!(matches!(
+            short.intersect(&long),
+            Err(DurationError::DurationValidationMinMustBeLessOrEqualMax { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_without_unit() {
+        assert!(matches!(
+            DurationHuman::parse_lenient("half an"),
+            Err(DurationError::InvalidSyntax)
+        ));
+        assert!(matches!(
+            DurationHuman::parse_lenient("half"),
+            Err(DurationError::InvalidSyntax)
+        ));
+        assert!(matches!(
+            DurationHuman::parse_lenient("1Ms"),
+            Err(DurationError::InvalidSyntax)
+        ));
+    }
+
+    #[test]
+    fn negative() {
+        for input in ["-5min", "-0s 1ns", "+", "-", "+-5min", "5min -"] {
+            assert!(
+                matches!(
+                    DurationHuman::try_from(input),
+                    Err(DurationError::InvalidSyntax)
+                ),
+                "{input} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn from_reader_not_utf8() {
+        assert!(matches!(
+            DurationHuman::from_reader(std::io::Cursor::new(b"5\xffmin".as_slice())),
+            Err(DurationError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn from_reader_too_long() {
+        let padded = format!(
+            "5min{}",
+            " ".repeat(usize::try_from(DurationHuman::MAX_IMPORT_BYTES).unwrap())
+        );
+        assert!(matches!(
+            DurationHuman::from_reader(std::io::Cursor::new(padded.as_bytes())),
+            Err(DurationError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn malformed_range() {
+        for range in ["1h..", "..1h", "1h", "1h...2h"] {
+            assert!(
+                DurationHumanValidator::parse_range(range).is_err(),
+                "{range} should be rejected"
+            );
+        }
+        assert!(matches!(
+            DurationHumanValidator::parse_range("1h.."),
+            Err(DurationError::InvalidRangeSyntax { range }) if range == "1h.."
+        ));
+    }
+
+    #[test]
+    fn std_out_of_range() -> Result<(), DurationError> {
+        let validator = DurationHumanValidator::try_from(("1min", "5min", "1h"))?;
+        for duration in [
+            std::time::Duration::from_secs(59),
+            std::time::Duration::from_secs(3601),
+        ] {
+            assert!(matches!(
+                validator.validate_std(duration),
+                Err(DurationError::DurationMustLieBetween { .. })
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn default_out_of_range() -> Result<(), DurationError> {
+        let validator = DurationHumanValidator::try_from(("1min", "5min", "1h"))?;
+        assert!(matches!(
+            validator.with_default(DurationHuman::try_from("2h")?),
+            Err(DurationError::DurationMustLieBetween { .. })
+        ));
+        Ok(())
+    }
 }
@@ -0,0 +1,35 @@
+use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+
+use crate::{DurationError, DurationHuman};
+
+impl DurationHuman {
+    /// The duration from `earlier` until `later`
+    ///
+    /// ## Errors
+    /// `DurationError::NegativeDuration` when `later` lies before `earlier`
+    pub fn between(earlier: DateTime<Utc>, later: DateTime<Utc>) -> Result<Self, DurationError> {
+        (later - earlier)
+            .to_std()
+            .map(Self::from)
+            .map_err(|_| DurationError::NegativeDuration {
+                earlier: earlier.to_rfc3339(),
+                later: later.to_rfc3339(),
+            })
+    }
+
+    /// The duration from `now` until the next time the clock shows `time`, e.g. a daily deadline;
+    /// when `time` already passed today, that is tomorrow
+    #[must_use]
+    pub fn until_next(time: NaiveTime, now: DateTime<Utc>) -> Self {
+        let today = Utc.from_utc_datetime(&now.date_naive().and_time(time));
+        let next = if today < now {
+            today + chrono::Duration::days(1)
+        } else {
+            today
+        };
+
+        (next - now)
+            .to_std()
+            .map_or_else(|_| Self::new(0), Self::from)
+    }
+}
@@ -44,6 +44,24 @@ impl DurationHumanValidator {
         }
     }
 
+    /// Parse a range like "1min..1h" or "1min..=1h", which both include the maximum,
+    /// with the default halfway
+    ///
+    /// ## Errors
+    /// `DurationError::InvalidRangeSyntax` when either side is missing, or the error of
+    /// parsing a side or validating the range
+    pub fn parse_range(range: &str) -> Result<Self, DurationError> {
+        let (minimal, maximal) = range
+            .split_once("..=")
+            .or_else(|| range.split_once(".."))
+            .filter(|(minimal, maximal)| !minimal.trim().is_empty() && !maximal.trim().is_empty())
+            .ok_or_else(|| DurationError::InvalidRangeSyntax {
+                range: range.to_string(),
+            })?;
+
+        Self::try_from((minimal.trim(), maximal.trim()))
+    }
+
     /// To be used as a `validate_parser` for clap
     ///
     /// ```compile_error
@@ -56,8 +74,17 @@ impl DurationHumanValidator {
     pub fn parse_and_validate(&self, duration: &str) -> Result<DurationHuman, DurationError> {
         let duration_in_nanos = DurationHuman::try_from(duration)?;
 
-        if self.contains(&duration_in_nanos) {
-            Ok(duration_in_nanos)
+        self.validate(&duration_in_nanos)
+            .map(|()| duration_in_nanos)
+    }
+
+    /// Check a duration that is already parsed
+    ///
+    /// ## Errors
+    /// `DurationError::DurationMustLieBetween` when the duration is not within the range
+    pub fn validate(&self, duration: &DurationHuman) -> Result<(), DurationError> {
+        if self.contains(duration) {
+            Ok(())
         } else {
             Err(DurationError::DurationMustLieBetween {
                 range: self.to_string(),
@@ -65,6 +92,56 @@ impl DurationHumanValidator {
         }
     }
 
+    /// Check a `std::time::Duration`, without formatting and parsing it again
+    ///
+    /// ## Errors
+    /// `DurationError::DurationMustLieBetween` when the duration is not within the range
+    pub fn validate_std(&self, duration: std::time::Duration) -> Result<(), DurationError> {
+        self.validate(&DurationHuman::from(duration))
+    }
+
+    /// The range that lies within both validators, with this default clamped into it
+    ///
+    /// ## Errors
+    /// `DurationError::DurationValidationMinMustBeLessOrEqualMax` when the ranges do not overlap
+    pub fn intersect(&self, other: &Self) -> Result<Self, DurationError> {
+        let min = self.min.max(other.min);
+        let max = self.max.min(other.max);
+
+        if min > max {
+            Err(DurationError::DurationValidationMinMustBeLessOrEqualMax {
+                minimal: min.to_string(),
+                maximal: max.to_string(),
+            })
+        } else {
+            Ok(Self {
+                min,
+                default: self.default.clamp(min, max),
+                max,
+            })
+        }
+    }
+
+    /// The same range with another default
+    ///
+    /// ## Errors
+    /// `DurationError::DurationMustLieBetween` when the default is not within the range
+    pub fn with_default(self, default: DurationHuman) -> Result<Self, DurationError> {
+        self.validate(&default).map(|()| Self { default, ..self })
+    }
+
+    /// The default, or the minimum when the default was set below it
+    #[must_use]
+    pub fn default_or_min(&self) -> DurationHuman {
+        self.default.max(self.min)
+    }
+
+    /// The range in words, as used in help texts and error messages: "between {min} and {max}"
+    #[must_use]
+    pub fn describe_range(&self) -> String {
+        format!("between {min} and {max}", min = self.min, max = self.max)
+    }
+
     #[must_use]
     pub fn contains(&self, duration: &DurationHuman) -> bool {
         self.min <= *duration && *duration <= self.max
@@ -94,6 +171,7 @@ impl From<&DurationHumanValidator> for (String, String, String) {
 impl TryFrom<(DurationHuman, DurationHuman)> for DurationHumanValidator {
     type Error = DurationError;
 
+    /// A range from the minimal to the maximal duration, which defaults to halfway between them
     fn try_from(value: (DurationHuman, DurationHuman)) -> Result<Self, Self::Error> {
         let (minimal, maximal) = &value;
         if minimal > maximal {
@@ -102,8 +180,12 @@ impl TryFrom<(DurationHuman, DurationHuman)> for DurationHumanValidator {
                 maximal: maximal.to_string(),
             })
         } else {
-            let (minimal_ms, maximal_ms): (u64, u64) = (minimal.into(), maximal.into());
-            Self::try_new(minimal_ms, minimal_ms, maximal_ms)
+            let (minimal_ms, default_ms, maximal_ms): (u64, u64, u64) = (
+                minimal.into(),
+                (&DurationHuman::midpoint(*minimal, *maximal)).into(),
+                maximal.into(),
+            );
+            Self::try_new(minimal_ms, default_ms, maximal_ms)
         }
     }
 }
@@ -6,51 +6,152 @@
     clippy::unwrap_used,
     clippy::expect_used
 )]
-use std::{fmt::Display, io, net::SocketAddr, sync::Arc};
+use std::{
+    fmt::Display, io, net::SocketAddr, num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration,
+};
 
 use axum::{
-    routing::{get, head, post},
-    Router,
+    error_handling::HandleErrorLayer,
+    handler::Handler,
+    middleware,
+    routing::{delete, get, post},
+    BoxError, Json, Router,
 };
-use axum_server::Handle;
+use axum_server::{AddrIncomingConfig, Handle, HttpConfig};
 use clap::Parser;
 use clap_duration::assign_duration_range_validator;
-use tokio::time::sleep;
-use tower::ServiceBuilder;
-use tower_http::trace::TraceLayer;
+use http::{header::CONTENT_TYPE, HeaderValue, Method, StatusCode};
+use tokio::{task::JoinHandle, time::sleep};
+use tower::{
+    limit::GlobalConcurrencyLimitLayer,
+    timeout::{error::Elapsed, TimeoutLayer},
+    ServiceBuilder,
+};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, Any, CorsLayer},
+    trace::TraceLayer,
+};
 use tracing::{debug, enabled, error, info, trace, warn, Level};
+use tracing_subscriber::{
+    filter::{Builder as EnvFilterBuilder, LevelFilter},
+    EnvFilter,
+};
 
 use duration_human::{DurationHuman, DurationHumanValidator};
 
 mod token_server;
-use token_server::{routes, TokenStore};
+use token_server::{
+    api::ServerConfig, limit_create_rate, routes, serve_unix, AuditLog, CreateRate, RateLimiter,
+    Rejection, TokenStore, Webhooks,
+};
 
 assign_duration_range_validator!( TOKEN_LIFETIME_RANGE = {default: 2h, min: 10min, max: 60day});
 assign_duration_range_validator!( PURGE_INTERVAL_RANGE = {min: 1500ms, default: 1min, max: 90min});
 
 #[derive(Parser)]
+#[allow(clippy::struct_excessive_bools)] // command line switches
+#[allow(clippy::doc_markdown)] // the doc comments are the --help text
 struct ServerOptions {
-    /// allow for HEAD /dump endpoint to log all metadata
+    /// allow for GET /dump endpoint to return, and HEAD /dump to log all metadata
     #[arg(long)]
     dump_enabled: bool,
 
+    /// dump the metadata as indented JSON
+    #[arg(long)]
+    dump_pretty: bool,
+
+    /// gzip the responses for clients that send Accept-Encoding: gzip, e.g. for GET /dump
+    #[arg(long)]
+    compress: bool,
+
     /// allow for GET /shutdown endpoint to shutdown this server
     #[arg(long)]
     shutdown_enabled: bool,
 
+    /// allow for the DELETE /admin/tokens, DELETE /admin/tags/:tag,
+    /// POST /admin/token/:id/extend and POST /admin/token/:id/rekey endpoints, and
+    /// GET /tokens/:value with --index-key
+    #[arg(long, conflicts_with = "read_only")]
+    admin_enabled: bool,
+
+    /// do not register the POST, PUT, PATCH and DELETE /token and POST /token/upsert endpoints
+    #[arg(long)]
+    read_only: bool,
+
     /// Which port to listen on
     #[arg(short, long, default_value_t = 3666, value_parser = clap::value_parser!(u16).range(3000..) ) ]
     port: u16,
 
-    /// What frequency to remove expired tokens
+    /// Listen on this Unix domain socket instead of a TCP port, e.g. behind a local reverse
+    /// proxy; the socket file is removed on shutdown
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["port", "create_rate", "tcp_keepalive"])]
+    uds: Option<PathBuf>,
+
+    /// Accept a webhook_url on POST /token, to POST the token and its metadata to once the
+    /// purge removed it expired; at most this many expiries wait to be sent
+    #[arg(long, value_name = "N")]
+    webhook_queue: Option<NonZeroUsize>,
+
+    /// Accept a webhook_url on this host, even though it is a loopback, private or link-local
+    /// address or resolves to one; repeat for more hosts
+    #[arg(long, value_name = "HOST", requires = "webhook_queue")]
+    webhook_allow_host: Vec<String>,
+
+    /// Respond with 503 Service Unavailable when more requests are being handled at once
+    #[arg(long)]
+    max_concurrent_requests: Option<NonZeroUsize>,
+
+    /// Respond to POST /token with 503 Service Unavailable once this many tokens are held,
+    /// while the existing tokens remain usable
+    #[arg(long)]
+    soft_cap: Option<NonZeroUsize>,
+
+    /// Respond with 408 Request Timeout when handling a request takes longer, e.g. 10s
+    #[arg(long, value_parser = {|timeout: &str|DurationHuman::parse(timeout)})]
+    request_timeout: Option<DurationHuman>,
+
+    /// Close the connection when a client does not send the complete request headers
+    /// within this time, e.g. 5s against slowloris-style clients
+    #[arg(long, value_parser = {|timeout: &str|DurationHuman::parse(timeout)})]
+    header_timeout: Option<DurationHuman>,
+
+    /// Probe idle TCP connections with SO_KEEPALIVE after this time, e.g. 1min, so
+    /// connections to vanished clients are closed; HTTP keep-alive is always enabled
+    #[arg(long, value_parser = {|keepalive: &str|DurationHuman::parse(keepalive)})]
+    tcp_keepalive: Option<DurationHuman>,
+
+    /// Append token lifecycle events, with hashed token ids, to this file
+    #[arg(long)]
+    audit_log: Option<PathBuf>,
+
+    /// Index tokens on the value of this metadata field, for GET /tokens/:value with
+    /// --admin-enabled
+    #[arg(long)]
+    index_key: Option<String>,
+
+    /// Respond to POST /token with 409 Conflict when a valid token has the same value for
+    /// this metadata field
+    #[arg(long)]
+    unique_key: Option<String>,
+
+    /// What frequency to remove expired tokens, 0s disables the automatic purge
     #[arg(
         long,
-        help = format!("What frequency to remove expired tokens, {}", PURGE_INTERVAL_RANGE),
+        help = format!("What frequency to remove expired tokens, {}; or 0s to disable the automatic purge", PURGE_INTERVAL_RANGE),
         default_value = PURGE_INTERVAL_RANGE.default,
-        value_parser = {|interval: &str|PURGE_INTERVAL_RANGE.parse_and_validate(interval)}
+        value_parser = {|interval: &str| match DurationHuman::parse(interval) {
+            Ok(interval) if Duration::from(&interval).is_zero() => Ok(interval),
+            _ => PURGE_INTERVAL_RANGE.parse_and_validate(interval),
+        }}
     )]
     purge_interval: DurationHuman,
 
+    /// refuse to start, rather than warn, when the options do not make sense together,
+    /// e.g. a purge interval longer than the token lifetime
+    #[arg(long)]
+    strict_config: bool,
+
     /// How long does a token remain valid
     #[arg(
         long,
@@ -59,75 +160,372 @@ struct ServerOptions {
         value_parser = {|lifetime: &str|TOKEN_LIFETIME_RANGE.parse_and_validate(lifetime)}
     )]
     token_lifetime: DurationHuman,
+
+    /// Respond to POST /token with 429 Too Many Requests when one client IP creates more
+    /// tokens than this rate, e.g. 100/1min
+    #[arg(long, value_parser = {|rate: &str|CreateRate::parse(rate)})]
+    create_rate: Option<CreateRate>,
+
+    /// Allow browser clients from this origin to call the server; repeat for more origins,
+    /// or use * to allow any origin
+    #[arg(
+        long,
+        value_parser = {|origin: &str|HeaderValue::from_str(origin).map(|_| origin.to_string())}
+    )]
+    cors_origin: Vec<String>,
+
+    /// Log at this level: trace, debug, info, warn or error; RUST_LOG can still set other
+    /// levels per target
+    #[arg(long)]
+    log_level: Option<Level>,
+
+    /// Minimal lifetime of a token created with an explicit expiry, sooner expiries are postponed
+    #[arg(
+        long,
+        default_value = "0s",
+        value_parser = {|lifetime: &str|DurationHuman::parse(lifetime)}
+    )]
+    min_token_lifetime: DurationHuman,
+
+    /// Tokens remain valid this long past their expiry, until the next purge removes them
+    #[arg(
+        long,
+        default_value = "0s",
+        value_parser = {|grace: &str|DurationHuman::parse(grace)}
+    )]
+    grace: DurationHuman,
+
+    /// Warn when the monotonic and wall clock drifted apart further than this, as checked
+    /// every purge cycle and on each dump
+    #[arg(
+        long,
+        default_value = "1s",
+        value_parser = {|drift: &str|DurationHuman::parse(drift)}
+    )]
+    max_clock_drift: DurationHuman,
 }
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
-    tracing_subscriber::fmt::init();
-
     let opts = ServerOptions::parse();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(log_filter(opts.log_level))
+        .init();
+
+    check_config(&opts)?;
+
     info!("Token server listening: {}", opts);
 
     let handle = Handle::new();
     let log_debug_enabled = enabled!(Level::DEBUG);
     let addr = SocketAddr::from(([127, 0, 0, 1], opts.port));
+    let audit_log = opts.audit_log.as_deref().map(AuditLog::open).transpose()?;
+    let webhooks = opts.webhook_queue.map(|capacity| {
+        let (webhooks, dispatcher) = Webhooks::new(capacity, opts.webhook_allow_host.clone());
+        tokio::spawn(dispatcher.run());
+        webhooks
+    });
     let token_store = Arc::new(
         TokenStore::default()
             .with_token_lifetime(opts.token_lifetime)
-            .with_handle(handle.clone()),
+            .with_min_lifetime(opts.min_token_lifetime)
+            .with_max_lifetime(TOKEN_LIFETIME_RANGE.max)
+            .with_grace(opts.grace)
+            .with_max_clock_drift(opts.max_clock_drift)
+            .with_soft_cap(opts.soft_cap)
+            .with_dump_pretty(opts.dump_pretty)
+            .with_handle(handle.clone())
+            .with_audit_log(audit_log)
+            .with_webhooks(webhooks)
+            .with_index_key(opts.index_key.clone())
+            .with_unique_key(opts.unique_key.clone()),
     );
-    let token_store_during_purge = token_store.clone();
+    let token_store_on_shutdown = token_store.clone();
+    let create_rate_limiter = opts
+        .create_rate
+        .map(|rate| Arc::new(RateLimiter::new(rate)));
 
-    tokio::spawn(async move {
+    spawn_purge(
+        opts.purge_interval,
+        token_store.clone(),
+        create_rate_limiter.clone(),
+        log_debug_enabled,
+    );
+
+    let app = with_layers(
+        enabled_routes(
+            &opts,
+            create_rate_limiter.map(|limiter| (limiter, token_store.clone())),
+            log_debug_enabled,
+        ),
+        &opts,
+        token_store,
+    );
+
+    let header_timeout = opts.header_timeout.map(|timeout| Duration::from(&timeout));
+    if let Some(ref path) = opts.uds {
+        serve_unix(path, app, header_timeout, async move {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {},
+                () = token_store_on_shutdown.shutdown_requested() => {},
+            }
+        })
+        .await?;
+    } else {
+        let mut http_config = HttpConfig::new();
+        if let Some(timeout) = header_timeout {
+            http_config.http1_header_read_timeout(timeout);
+        }
+
+        axum_server::bind(addr)
+            .handle(handle)
+            .http_config(http_config.build())
+            .addr_incoming_config(
+                AddrIncomingConfig::new()
+                    .tcp_keepalive(
+                        opts.tcp_keepalive
+                            .map(|keepalive| Duration::from(&keepalive)),
+                    )
+                    .build(),
+            )
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Remove the expired tokens every purge interval, unless that is zero
+fn spawn_purge(
+    purge_interval: DurationHuman,
+    token_store: Arc<TokenStore>,
+    create_rate_limiter: Option<Arc<RateLimiter>>,
+    log_debug_enabled: bool,
+) -> Option<JoinHandle<()>> {
+    if Duration::from(&purge_interval).is_zero() {
+        warn!("Automatic purge is disabled: expired tokens are rejected, but stay in memory");
+        return None;
+    }
+
+    Some(tokio::spawn(async move {
         loop {
-            sleep((&opts.purge_interval).into()).await;
-
-            token_store_during_purge
-                .clone()
-                .remove_expired_tokens()
-                .map_or_else(
-                    |err| error!("PURGE failed: {}", err),
-                    |purged| {
-                        if log_debug_enabled && purged.purged > 0 {
-                            debug!("{}", purged);
-                        } else {
-                            trace!("{}", purged);
-                        }
-                    },
-                );
+            sleep((&purge_interval).into()).await;
+
+            token_store.check_clock_drift();
+
+            if let Some(ref limiter) = create_rate_limiter {
+                limiter.forget_idle();
+            }
+
+            let purged = token_store.remove_expired_tokens();
+            if log_debug_enabled && purged.purged > 0 {
+                debug!("{}", purged);
+            } else {
+                trace!("{}", purged);
+            }
         }
-    });
+    }))
+}
 
-    let mut token_server_routes = Router::new().route(
-        "/token",
-        post(routes::create_token)
-            .put(routes::update_token)
-            .delete(routes::remove_token),
-    );
+/// The routes for the enabled endpoints, before the layers and the token store are added;
+/// the create rate limiter comes with the token store that counts its rejections
+fn enabled_routes(
+    opts: &ServerOptions,
+    create_rate_limiter: Option<(Arc<RateLimiter>, Arc<TokenStore>)>,
+    log_debug_enabled: bool,
+) -> Router<Arc<TokenStore>> {
+    let server_config = ServerConfig::from(opts);
+    let mut token_server_routes = Router::new()
+        .route("/config", get(move || async move { Json(server_config) }))
+        .route("/metrics", get(routes::show_metrics))
+        .route("/tokens/validate", post(routes::validate_tokens));
 
-    if opts.dump_enabled && log_debug_enabled {
-        token_server_routes = token_server_routes.route("/dump", head(routes::dump_meta));
+    if opts.read_only {
+        warn!("Read-only mode: POST, PUT, PATCH and DELETE /token and POST /token/upsert are not available");
     } else {
-        warn!("HEAD /dump will not provide logging; use RUSTLOG='token_server=debug'");
+        let create_token = create_rate_limiter.map_or_else(
+            || post(routes::create_token),
+            |limit| {
+                post(
+                    routes::create_token
+                        .layer(middleware::from_fn_with_state(limit, limit_create_rate)),
+                )
+            },
+        );
+
+        token_server_routes = token_server_routes
+            .route(
+                "/token",
+                create_token
+                    .put(routes::update_token)
+                    .patch(routes::patch_token)
+                    .delete(routes::remove_token),
+            )
+            .route("/token/upsert", post(routes::upsert_token));
+    }
+
+    if opts.admin_enabled {
+        token_server_routes = token_server_routes
+            .route("/admin/tokens", delete(routes::clear_tokens))
+            .route("/admin/tags/:tag", delete(routes::remove_tokens_by_tag))
+            .route("/admin/token/:id/extend", post(routes::extend_token))
+            .route("/admin/token/:id/rekey", post(routes::rekey_token));
+    }
+
+    if opts.admin_enabled && opts.index_key.is_some() {
+        token_server_routes = token_server_routes.route("/tokens/:value", get(routes::find_tokens));
+    }
+
+    if opts.dump_enabled {
+        let mut dump = get(routes::dump_report);
+        if log_debug_enabled {
+            dump = dump.head(routes::dump_meta);
+        } else {
+            warn!("HEAD /dump will not provide logging; use RUSTLOG='token_server=debug', or GET /dump");
+        }
+        token_server_routes = token_server_routes.route("/dump", dump);
     }
 
     if opts.shutdown_enabled {
         token_server_routes = token_server_routes.route("/shutdown", get(routes::shutdown_server));
     }
 
-    axum_server::bind(addr)
-        .handle(handle)
-        .serve(
-            token_server_routes
-                .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()))
-                .with_state(token_store)
-                .into_make_service(),
-        )
-        .await?;
+    token_server_routes
+}
+
+/// The fallback for unknown routes and the layers around all routes, with the token store added
+fn with_layers(
+    token_server_routes: Router<Arc<TokenStore>>,
+    opts: &ServerOptions,
+    token_store: Arc<TokenStore>,
+) -> Router {
+    let token_store_when_overloaded = token_store.clone();
+
+    let mut router = token_server_routes.fallback(routes::not_found).layer(
+        ServiceBuilder::new()
+            .layer(TraceLayer::new_for_http())
+            .layer(HandleErrorLayer::new(move |err: BoxError| {
+                let (rejection, status) = if err.is::<Elapsed>() {
+                    (Rejection::TimedOut, StatusCode::REQUEST_TIMEOUT)
+                } else {
+                    (Rejection::Overloaded, StatusCode::SERVICE_UNAVAILABLE)
+                };
+                token_store_when_overloaded.metrics().reject(rejection);
+                async move { status }
+            }))
+            .option_layer(
+                opts.request_timeout
+                    .map(|timeout| TimeoutLayer::new((&timeout).into())),
+            )
+            .load_shed()
+            .option_layer(
+                opts.max_concurrent_requests
+                    .map(|limit| GlobalConcurrencyLimitLayer::new(limit.get())),
+            ),
+    );
+    // not in the ServiceBuilder, where an optional layer would box the errors that
+    // HandleErrorLayer made infallible
+    if let Some(cors) = cors_layer(&opts.cors_origin) {
+        router = router.layer(cors);
+    }
+    // compression changes the response body type, so it is no optional layer either
+    if opts.compress {
+        router = router.layer(CompressionLayer::new());
+    }
+
+    router.with_state(token_store)
+}
+
+/// Warn about options that are valid on their own, but wasteful together;
+/// with --strict-config these refuse to start instead
+fn check_config(opts: &ServerOptions) -> io::Result<()> {
+    if let Some(message) = config_warning(opts) {
+        if opts.strict_config {
+            error!("{}", message);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        warn!("{}", message);
+    }
 
     Ok(())
 }
 
+/// Why the options do not make sense together, if they don't
+fn config_warning(opts: &ServerOptions) -> Option<String> {
+    (!Duration::from(&opts.purge_interval).is_zero() && opts.purge_interval > opts.token_lifetime)
+        .then(|| {
+            format!(
+                "Purge cycle {:#} is longer than the token lifetime {:#}: expired tokens stay in memory until the next purge",
+                opts.purge_interval, opts.token_lifetime
+            )
+        })
+}
+
+/// Without a log level, only `RUST_LOG` decides what to log, as before the option existed
+fn log_filter(log_level: Option<Level>) -> EnvFilter {
+    log_level.map_or_else(EnvFilter::from_default_env, |log_level| {
+        log_level_filter(log_level).from_env_lossy()
+    })
+}
+
+/// Everything at this level or above, before `RUST_LOG` adds its directives per target
+fn log_level_filter(log_level: Level) -> EnvFilterBuilder {
+    EnvFilter::builder().with_default_directive(LevelFilter::from_level(log_level).into())
+}
+
+/// Answer CORS preflights for the configured origins, with the methods the routes use
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        warn!("CORS allows any origin to call the token server");
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([CONTENT_TYPE]),
+    )
+}
+
+impl From<&ServerOptions> for ServerConfig {
+    fn from(opts: &ServerOptions) -> Self {
+        Self {
+            token_lifetime: opts.token_lifetime.to_string(),
+            read_only: opts.read_only,
+            admin: opts.admin_enabled,
+            dump: opts.dump_enabled,
+            shutdown: opts.shutdown_enabled,
+            index_key: opts.index_key.clone(),
+            unique_key: opts.unique_key.clone(),
+            compress: opts.compress,
+            cors: !opts.cors_origin.is_empty(),
+            create_rate: opts.create_rate.map(|rate| rate.to_string()),
+            soft_cap: opts.soft_cap.map(NonZeroUsize::get),
+            request_timeout: opts.request_timeout.map(|timeout| timeout.to_string()),
+            webhook_queue: opts.webhook_queue.map(NonZeroUsize::get),
+        }
+    }
+}
+
 impl Display for ServerOptions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         #[inline]
@@ -136,12 +534,841 @@ impl Display for ServerOptions {
         }
 
         f.write_fmt(format_args!(
-            "Port: {portnumber}, Token lifetime: {lifetime:#}, Purge cycle: {interval:#}, HEAD /dump {dump_enabled}, GET /shutdown {shutdown_enabled}",
-            portnumber = self.port,
+            "Listen: {listen}, Token lifetime: {lifetime:#}, Min token lifetime: {min_lifetime}, Grace: {grace}, Max clock drift: {max_clock_drift}, Purge cycle: {interval}, GET/HEAD /dump {dump_enabled}, Pretty dump {dump_pretty}, Compression {compress}, GET /shutdown {shutdown_enabled}, Admin {admin_enabled}, Read-only {read_only}, Strict config {strict_config}, Max concurrent requests: {max_concurrent_requests}, Request timeout: {request_timeout}, Header timeout: {header_timeout}, TCP keep-alive: {tcp_keepalive}, Soft cap: {soft_cap}, Audit log: {audit_log}, Webhook queue: {webhook_queue}, Webhook allowed hosts: {webhook_allow_host}, Index key: {index_key}, Unique key: {unique_key}, Create rate: {create_rate}, CORS origins: {cors_origins}, Log level: {log_level}",
+            listen = self.uds.as_ref().map_or_else(
+                || format!("127.0.0.1:{}", self.port),
+                |path| format!("unix:{}", path.display())
+            ),
             lifetime=self.token_lifetime,
-            interval=self.purge_interval,
+            min_lifetime = self.min_token_lifetime,
+            grace = self.grace,
+            max_clock_drift = self.max_clock_drift,
+            interval = if Duration::from(&self.purge_interval).is_zero() {
+                String::from("disabled")
+            } else {
+                format!("{:#}", self.purge_interval)
+            },
             dump_enabled = is_enabled(self.dump_enabled),
-            shutdown_enabled = is_enabled(self.shutdown_enabled)
+            dump_pretty = is_enabled(self.dump_pretty),
+            compress = is_enabled(self.compress),
+            shutdown_enabled = is_enabled(self.shutdown_enabled),
+            admin_enabled = is_enabled(self.admin_enabled),
+            read_only = is_enabled(self.read_only),
+            strict_config = is_enabled(self.strict_config),
+            max_concurrent_requests = self
+                .max_concurrent_requests
+                .map_or_else(|| String::from("unlimited"), |limit| limit.to_string()),
+            request_timeout = self
+                .request_timeout
+                .map_or_else(|| String::from("unlimited"), |timeout| timeout.to_string()),
+            header_timeout = self
+                .header_timeout
+                .map_or_else(|| String::from("unlimited"), |timeout| timeout.to_string()),
+            tcp_keepalive = self
+                .tcp_keepalive
+                .map_or_else(|| String::from("disabled"), |keepalive| keepalive.to_string()),
+            soft_cap = self
+                .soft_cap
+                .map_or_else(|| String::from("unlimited"), |soft_cap| soft_cap.to_string()),
+            audit_log = self
+                .audit_log
+                .as_ref()
+                .map_or_else(|| String::from("disabled"), |path| path.display().to_string()),
+            webhook_queue = self
+                .webhook_queue
+                .map_or_else(|| String::from("disabled"), |capacity| capacity.to_string()),
+            webhook_allow_host = if self.webhook_allow_host.is_empty() {
+                String::from("none")
+            } else {
+                self.webhook_allow_host.join(", ")
+            },
+            index_key = self.index_key.as_deref().unwrap_or("disabled"),
+            unique_key = self.unique_key.as_deref().unwrap_or("disabled"),
+            create_rate = self
+                .create_rate
+                .map_or_else(|| String::from("unlimited"), |rate| rate.to_string()),
+            log_level = self
+                .log_level
+                .map_or_else(|| String::from("RUST_LOG"), |level| level.to_string()),
+            cors_origins = if self.cors_origin.is_empty() {
+                String::from("disabled")
+            } else {
+                self.cors_origin.join(", ")
+            }
         ))
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_in_result, clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use std::{error::Error, net::SocketAddr, sync::Arc, time::Duration};
+
+    use axum::{body::Body, extract::ConnectInfo, routing::get, Router};
+    use clap::Parser;
+    use duration_human::DurationHuman;
+    use http::{HeaderValue, Method, Request, StatusCode};
+    use serde_json::json;
+    use tokio::time::sleep;
+    use tower::ServiceExt;
+    use tracing::Level;
+
+    use super::{
+        check_config, config_warning, enabled_routes, log_level_filter, spawn_purge,
+        token_server::{api::MetaData, InvalidExpiry, RateLimiter, Rejection},
+        with_layers, ServerOptions, TokenStore,
+    };
+
+    type TestResult = Result<(), Box<dyn Error>>;
+
+    fn app(args: &[&str], token_store: Arc<TokenStore>) -> Result<Router, Box<dyn Error>> {
+        let opts = ServerOptions::try_parse_from(
+            std::iter::once("token_server").chain(args.iter().copied()),
+        )?;
+
+        Ok(enabled_routes(&opts, None, false).with_state(token_store))
+    }
+
+    async fn send(
+        app: Router,
+        method: Method,
+        uri: &str,
+    ) -> Result<(StatusCode, String), Box<dyn Error>> {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())?;
+
+        respond(app, request).await
+    }
+
+    async fn send_json(
+        app: Router,
+        method: Method,
+        uri: &str,
+        body: &serde_json::Value,
+    ) -> Result<(StatusCode, String), Box<dyn Error>> {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))?;
+
+        respond(app, request).await
+    }
+
+    async fn respond(
+        app: Router,
+        request: Request<Body>,
+    ) -> Result<(StatusCode, String), Box<dyn Error>> {
+        let response = app.oneshot(request).await?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+
+        Ok((status, String::from_utf8(body.to_vec())?))
+    }
+
+    #[tokio::test]
+    async fn read_only_still_validates() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let token = token_store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+
+        for (method, body) in [
+            (Method::POST, json!({ "meta": {} })),
+            (Method::PUT, json!({ "token": token })),
+            (Method::PATCH, json!({ "token": token, "meta": {} })),
+            (Method::DELETE, json!({ "token": token })),
+        ] {
+            let app = app(&["--read-only"], Arc::clone(&token_store))?;
+            let (status, _body) = send_json(app, method.clone(), "/token", &body).await?;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{method}");
+        }
+        let upsert = json!({ "key": "user", "value": "alice", "meta": {} });
+        let read_only = app(&["--read-only"], Arc::clone(&token_store))?;
+        let (status, _body) = send_json(read_only, Method::POST, "/token/upsert", &upsert).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let read_only = app(&["--read-only"], Arc::clone(&token_store))?;
+        let validate = json!({ "tokens": [token] });
+        let (status, body) =
+            send_json(read_only, Method::POST, "/tokens/validate", &validate).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body)?["results"][&token]["valid"],
+            json!(true)
+        );
+
+        let opts = ServerOptions::try_parse_from(["token_server", "--read-only"])?;
+        assert!(opts.to_string().contains("Read-only enabled"));
+        Ok(())
+    }
+
+    /// A route that takes this long to respond, to hold on to a request
+    fn slow_routes(delay: Duration) -> Router<Arc<TokenStore>> {
+        Router::new().route(
+            "/slow",
+            get(move || async move {
+                sleep(delay).await;
+                "done"
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn requests_beyond_the_concurrency_limit_are_shed() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let opts =
+            ServerOptions::try_parse_from(["token_server", "--max-concurrent-requests", "1"])?;
+        let app = with_layers(
+            slow_routes(Duration::from_millis(200)),
+            &opts,
+            Arc::clone(&token_store),
+        );
+
+        let (within_limit, beyond_limit) =
+            tokio::join!(send(app.clone(), Method::GET, "/slow"), async {
+                sleep(Duration::from_millis(50)).await;
+                send(app.clone(), Method::GET, "/slow").await
+            });
+        let (within_limit, body) = within_limit?;
+        assert_eq!((within_limit, body.as_str()), (StatusCode::OK, "done"));
+        assert_eq!(beyond_limit?.0, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(token_store.metrics().rejected(Rejection::Overloaded), 1);
+
+        let (after, _body) = send(app, Method::GET, "/slow").await?;
+        assert_eq!(after, StatusCode::OK);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requests_beyond_the_request_timeout_time_out() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let opts = ServerOptions::try_parse_from(["token_server", "--request-timeout", "50ms"])?;
+        let app = with_layers(
+            slow_routes(Duration::from_millis(200)),
+            &opts,
+            Arc::clone(&token_store),
+        );
+
+        let (status, _body) = send(app, Method::GET, "/slow").await?;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(token_store.metrics().rejected(Rejection::TimedOut), 1);
+        assert_eq!(token_store.metrics().rejected(Rejection::Overloaded), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_rate_is_limited_per_client_ip() -> TestResult {
+        let opts = ServerOptions::try_parse_from(["token_server", "--create-rate", "2/1min"])?;
+        let token_store = Arc::new(TokenStore::default());
+        let limiter = opts
+            .create_rate
+            .map(|rate| (Arc::new(RateLimiter::new(rate)), Arc::clone(&token_store)));
+        let app = enabled_routes(&opts, limiter, false).with_state(Arc::clone(&token_store));
+
+        let create = |client: [u8; 4]| {
+            let mut request = Request::builder()
+                .method(Method::POST)
+                .uri("/token")
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "meta": {} }).to_string()))?;
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from((client, 40000))));
+
+            Ok::<_, Box<dyn Error>>(app.clone().oneshot(request))
+        };
+
+        for _ in 0..2 {
+            assert_eq!(create([10, 0, 0, 1])?.await?.status(), StatusCode::OK);
+        }
+        let rejected = create([10, 0, 0, 1])?.await?;
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = rejected
+            .headers()
+            .get(http::header::RETRY_AFTER)
+            .ok_or("no Retry-After header")?
+            .to_str()?
+            .parse::<u64>()?;
+        assert!((1..=30).contains(&retry_after), "{retry_after}");
+        assert_eq!(token_store.metrics().rejected(Rejection::RateLimited), 1);
+
+        assert_eq!(create([10, 0, 0, 2])?.await?.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cors_allows_the_listed_origins() -> TestResult {
+        let args = ["token_server", "--cors-origin", "https://app.example.com"];
+        let opts = ServerOptions::try_parse_from(args)?;
+        let app = with_layers(enabled_routes(&opts, None, false), &opts, Arc::default());
+        let allowed_origin = |response: &http::Response<_>| {
+            response
+                .headers()
+                .get(http::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .cloned()
+        };
+
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/token")
+            .header(http::header::ORIGIN, "https://app.example.com")
+            .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())?;
+        let response = app.clone().oneshot(preflight).await?;
+        assert!(response.status().is_success());
+        assert_eq!(
+            allowed_origin(&response)
+                .as_ref()
+                .map(HeaderValue::as_bytes),
+            Some(&b"https://app.example.com"[..])
+        );
+
+        for (origin, allowed) in [
+            ("https://app.example.com", true),
+            ("https://evil.example.com", false),
+        ] {
+            let request = Request::builder()
+                .uri("/config")
+                .header(http::header::ORIGIN, origin)
+                .body(Body::empty())?;
+            let response = app.clone().oneshot(request).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(allowed_origin(&response).is_some(), allowed, "{origin}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compress_gzips_the_dump() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        token_store.create_token_full(
+            json!({ "user": "alice", "team": "platform" })
+                .as_object()
+                .cloned()
+                .unwrap(),
+            Vec::new(),
+            None,
+        )?;
+
+        let opts = ServerOptions::try_parse_from(["token_server", "--dump-enabled", "--compress"])?;
+        let app = with_layers(enabled_routes(&opts, None, false), &opts, token_store);
+
+        let request = Request::builder()
+            .uri("/dump")
+            .header(http::header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get(http::header::CONTENT_ENCODING)
+                .map(HeaderValue::as_bytes),
+            Some(&b"gzip"[..])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn zero_purge_interval_leaves_expired_tokens_until_purged() -> TestResult {
+        let opts = ServerOptions::try_parse_from(["token_server", "--purge-interval", "0s"])?;
+        let token_store =
+            Arc::new(TokenStore::default().with_token_lifetime(DurationHuman::parse("1ms")?));
+        assert!(spawn_purge(opts.purge_interval, Arc::clone(&token_store), None, false).is_none());
+
+        let token = token_store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+        sleep(Duration::from_millis(20)).await;
+        assert!(!token_store.validate_many(std::slice::from_ref(&token))[&token].valid);
+
+        let purged = token_store.remove_expired_tokens();
+        assert_eq!((purged.purged, purged.tokens), (1, 0));
+
+        let purge = spawn_purge(DurationHuman::parse("1500ms")?, token_store, None, false);
+        purge
+            .ok_or("no purge task for a non-zero interval")?
+            .abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_respond_with_json() -> TestResult {
+        let opts = ServerOptions::try_parse_from(["token_server"])?;
+        let app = with_layers(enabled_routes(&opts, None, false), &opts, Arc::default());
+
+        let (status, body) = send(app, Method::GET, "/no/such/path?x=1").await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body)?,
+            json!({ "error": "not_found", "path": "/no/such/path" })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_token_responds_with_json() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let create = |uri: &str| {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "meta": { "user": "alice" } }).to_string(),
+                ))?;
+
+            Ok::<_, Box<dyn Error>>(app(&[], Arc::clone(&token_store))?.oneshot(request))
+        };
+
+        let response = create("/token")?.await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get(http::header::CONTENT_TYPE)
+                .map(HeaderValue::as_bytes),
+            Some(&b"application/json"[..])
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let created = serde_json::from_slice::<serde_json::Value>(&body)?;
+        let token = created["token"].as_str().ok_or("no token")?;
+        assert_eq!(created, json!({ "token": token }));
+        assert!(token_store.validate_many(&[token.to_string()])[token].valid);
+
+        let response = create("/token?full=true")?.await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let created = serde_json::from_slice::<serde_json::Value>(&body)?;
+        assert!(created["token"].is_string(), "{created}");
+        assert_eq!(created["meta"], json!({ "user": "alice" }));
+        assert!(
+            created["expires"]
+                .as_str()
+                .is_some_and(|expires| expires.parse::<chrono::DateTime<chrono::Utc>>().is_ok()),
+            "{created}"
+        );
+        assert_eq!(created.as_object().map(serde_json::Map::len), Some(3));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_token_expires_at_the_given_moment() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let router = || app(&[], Arc::clone(&token_store));
+        let now = chrono::Utc::now();
+        let past = (now - chrono::Duration::minutes(1)).to_rfc3339();
+        let future = now + chrono::Duration::minutes(10);
+
+        let past_body = json!({ "meta": {}, "expires_at": past });
+        let (status, body) = send_json(router()?, Method::POST, "/token", &past_body).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+
+        let both = json!({ "meta": {}, "expires": "5min", "expires_at": future.to_rfc3339() });
+        let (status, body) = send_json(router()?, Method::POST, "/token", &both).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, InvalidExpiry::Ambiguous.to_string());
+
+        let future_body = json!({ "meta": {}, "expires_at": future.to_rfc3339() });
+        let (status, body) = send_json(router()?, Method::POST, "/token", &future_body).await?;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(token_store.metrics().rejected(Rejection::InvalidExpiry), 2);
+
+        let dump = serde_json::from_str::<serde_json::Value>(&token_store.dump_report(None)?)?;
+        let dumped = dump[0]["expires"].as_str().ok_or("no expiry dumped")?;
+        let dumped = chrono::NaiveDateTime::parse_from_str(dumped, "%Y-%m-%d %H:%M:%S%.3f")?;
+        let drift = (dumped - future.naive_utc()).num_milliseconds().abs();
+        assert!(drift < 1000, "dumped {dumped}, asked for {future}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn large_metadata_numbers_keep_their_precision() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let large = "123456789012345678901234567890";
+        let precise = "0.1000000000000000055511151231257827";
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/token")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!(
+                r#"{{ "meta": {{ "account": {large}, "ratio": {precise} }} }}"#
+            )))?;
+        let (status, body) = respond(app(&[], Arc::clone(&token_store))?, request).await?;
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        let (status, dump) =
+            send(app(&["--dump-enabled"], token_store)?, Method::GET, "/dump").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert!(dump.contains(&format!(r#""account":{large}"#)), "{dump}");
+        assert!(dump.contains(&format!(r#""ratio":{precise}"#)), "{dump}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dump_returns_data_at_info_level() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        token_store.create_token_full(
+            json!({ "user": "alice" }).as_object().cloned().unwrap(),
+            Vec::new(),
+            None,
+        )?;
+
+        // without debug logging, as at the info level
+        let opts = ServerOptions::try_parse_from(["token_server", "--dump-enabled"])?;
+        let app = enabled_routes(&opts, None, false).with_state(token_store);
+
+        let (status, body) = send(app, Method::GET, "/dump").await?;
+        assert_eq!(status, StatusCode::OK);
+        let dump = serde_json::from_str::<serde_json::Value>(&body)?;
+        assert_eq!(dump[0]["meta"], json!({ "user": "alice" }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn admin_routes_only_with_admin_enabled() -> TestResult {
+        let admin_routes = [
+            (Method::DELETE, "/admin/tokens"),
+            (Method::DELETE, "/admin/tags/nightly"),
+            (Method::POST, "/admin/token/:token/extend"),
+            (Method::POST, "/admin/token/:token/rekey"),
+        ];
+
+        for (args, registered) in [(&[][..], false), (&["--admin-enabled"][..], true)] {
+            for (method, uri) in admin_routes.clone() {
+                // a valid token, so the handlers themselves do not respond with 404
+                let token_store = Arc::new(TokenStore::default());
+                let token = token_store
+                    .create_token_full(MetaData::new(), Vec::new(), None)?
+                    .token;
+                let uri = uri.replace(":token", &token);
+
+                let (status, _body) = send(app(args, token_store)?, method, &uri).await?;
+                assert_eq!(
+                    status != StatusCode::NOT_FOUND,
+                    registered,
+                    "{uri} with {args:?}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rekey_to_an_id_in_use_conflicts() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let token = token_store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+        let other = token_store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+        let router = || app(&["--admin-enabled"], Arc::clone(&token_store));
+
+        let uri = format!("/admin/token/{token}/rekey?new={other}");
+        let (status, body) = send(router()?, Method::POST, &uri).await?;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body, r#"{"Err":"TokenInUse"}"#);
+
+        let uri = format!("/admin/token/{token}/rekey?new=rotated");
+        let (status, body) = send(router()?, Method::POST, &uri).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"Ok":"rotated"}"#);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn find_tokens_only_with_admin_enabled() -> TestResult {
+        for (args, status) in [
+            (&["--index-key", "user"][..], StatusCode::NOT_FOUND),
+            (
+                &["--index-key", "user", "--admin-enabled"][..],
+                StatusCode::OK,
+            ),
+        ] {
+            let token_store =
+                Arc::new(TokenStore::default().with_index_key(Some(String::from("user"))));
+            token_store.create_token_full(
+                json!({ "user": "alice" }).as_object().cloned().unwrap(),
+                Vec::new(),
+                None,
+            )?;
+
+            let (found, _body) =
+                send(app(args, token_store)?, Method::GET, "/tokens/alice").await?;
+            assert_eq!(found, status, "{args:?}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unique_key_conflicts_respond_with_json() -> TestResult {
+        let token_store =
+            Arc::new(TokenStore::default().with_unique_key(Some(String::from("user"))));
+        token_store.create_token_full(
+            json!({ "user": "alice" }).as_object().cloned().unwrap(),
+            Vec::new(),
+            None,
+        )?;
+        let bob = token_store
+            .create_token_full(
+                json!({ "user": "bob" }).as_object().cloned().unwrap(),
+                Vec::new(),
+                None,
+            )?
+            .token;
+
+        for (method, uri, body) in [
+            (
+                Method::POST,
+                "/token",
+                json!({ "meta": { "user": "alice" } }),
+            ),
+            (
+                Method::PUT,
+                "/token",
+                json!({ "token": bob, "meta": { "user": "alice" } }),
+            ),
+            (
+                Method::PATCH,
+                "/token",
+                json!({ "token": bob, "meta": { "user": "alice" } }),
+            ),
+            (
+                Method::POST,
+                "/token/upsert",
+                json!({ "key": "user", "value": "bob", "meta": { "user": "alice" } }),
+            ),
+        ] {
+            let app = app(&["--unique-key", "user"], Arc::clone(&token_store))?;
+            let (status, body) = send_json(app, method, uri, &body).await?;
+            assert_eq!(status, StatusCode::CONFLICT, "{uri}");
+            assert!(
+                serde_json::from_str::<serde_json::Value>(&body)?
+                    .get("Err")
+                    .is_some(),
+                "{uri}: {body}"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bad_bodies_count_as_bad_input() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let token = token_store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/token")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{\"meta\": "))?;
+        let (status, _body) = respond(app(&[], Arc::clone(&token_store))?, request).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(token_store.metrics().rejected(Rejection::BadInput), 1);
+
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri("/token")
+            .header(http::header::CONTENT_TYPE, "text/plain")
+            .body(Body::from(json!({ "token": token }).to_string()))?;
+        let (status, _body) = respond(app(&[], Arc::clone(&token_store))?, request).await?;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(token_store.metrics().rejected(Rejection::BadInput), 2);
+
+        let app = app(&[], Arc::clone(&token_store))?;
+        let (_status, metrics) = send(app, Method::GET, "/metrics").await?;
+        assert!(metrics.contains("token_server_rejected_requests_total{reason=\"bad_input\"} 2"));
+        assert!(token_store.validate_many(std::slice::from_ref(&token))[&token].valid);
+        Ok(())
+    }
+
+    #[test]
+    fn tcp_keepalive_only_over_tcp() -> TestResult {
+        let opts = ServerOptions::try_parse_from(["token_server", "--tcp-keepalive", "1min"])?;
+        assert_eq!(opts.tcp_keepalive, Some(DurationHuman::parse("1min")?));
+        assert!(opts.to_string().contains("TCP keep-alive: 1min"));
+
+        assert!(ServerOptions::try_parse_from(["token_server", "--keepalive", "1min"]).is_err());
+        assert!(ServerOptions::try_parse_from([
+            "token_server",
+            "--tcp-keepalive",
+            "1min",
+            "--uds",
+            "/tmp/token_server.sock"
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn warns_for_a_purge_interval_longer_than_the_token_lifetime() -> TestResult {
+        let args = [
+            "token_server",
+            "--token-lifetime",
+            "30min",
+            "--purge-interval",
+            "90min",
+        ];
+
+        let opts = ServerOptions::try_parse_from(args)?;
+        let warning = config_warning(&opts).ok_or("no warning")?;
+        assert!(
+            warning.contains("longer than the token lifetime"),
+            "{warning}"
+        );
+        assert!(check_config(&opts).is_ok());
+
+        let opts = ServerOptions::try_parse_from(args.into_iter().chain(["--strict-config"]))?;
+        assert!(check_config(&opts).is_err());
+
+        for args in [
+            &["--token-lifetime", "2h", "--purge-interval", "1min"][..],
+            &["--token-lifetime", "30min", "--purge-interval", "0s"][..],
+        ] {
+            let opts = ServerOptions::try_parse_from(
+                std::iter::once("token_server").chain(args.iter().copied()),
+            )?;
+            assert!(config_warning(&opts).is_none(), "{args:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn log_level_sets_the_max_level() -> TestResult {
+        for (name, level) in [
+            ("trace", Level::TRACE),
+            ("debug", Level::DEBUG),
+            ("info", Level::INFO),
+            ("warn", Level::WARN),
+            ("error", Level::ERROR),
+        ] {
+            let opts = ServerOptions::try_parse_from(["token_server", "--log-level", name])?;
+            assert_eq!(opts.log_level, Some(level));
+
+            // without RUST_LOG from the environment, which may override the level per target
+            let subscriber = tracing_subscriber::fmt()
+                .with_env_filter(log_level_filter(level).parse_lossy(""))
+                .finish();
+            tracing::subscriber::with_default(subscriber, || {
+                assert!(tracing::enabled!(Level::ERROR), "{name}");
+                assert_eq!(tracing::enabled!(Level::TRACE), level == Level::TRACE);
+            });
+        }
+
+        assert!(ServerOptions::try_parse_from(["token_server", "--log-level", "verbose"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn admin_enabled_conflicts_with_read_only() {
+        assert!(
+            ServerOptions::try_parse_from(["token_server", "--admin-enabled", "--read-only"])
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn config_reports_admin() -> TestResult {
+        for (args, admin) in [(&[][..], false), (&["--admin-enabled"][..], true)] {
+            let (status, body) = send(app(args, Arc::default())?, Method::GET, "/config").await?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body)?["admin"],
+                json!(admin)
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn config_lists_the_enabled_endpoints_and_options() -> TestResult {
+        let config = |args: &'static [&'static str]| async move {
+            let (status, body) = send(app(args, Arc::default())?, Method::GET, "/config").await?;
+            assert_eq!(status, StatusCode::OK);
+            Ok::<_, Box<dyn Error>>(serde_json::from_str::<serde_json::Value>(&body)?)
+        };
+
+        let defaults = config(&[]).await?;
+        assert_eq!(
+            defaults,
+            json!({
+                "token_lifetime": ServerOptions::try_parse_from(["token_server"])?
+                    .token_lifetime
+                    .to_string(),
+                "read_only": false,
+                "admin": false,
+                "dump": false,
+                "shutdown": false,
+                "index_key": null,
+                "unique_key": null,
+                "compress": false,
+                "cors": false,
+                "create_rate": null,
+                "soft_cap": null,
+                "request_timeout": null,
+                "webhook_queue": null,
+            })
+        );
+
+        // still served in read-only mode, as it changes nothing
+        let configured = config(&[
+            "--read-only",
+            "--dump-enabled",
+            "--index-key",
+            "user",
+            "--cors-origin",
+            "https://app.example.com",
+            "--soft-cap",
+            "1000",
+            "--request-timeout",
+            "10s",
+        ])
+        .await?;
+        assert_eq!(configured["read_only"], json!(true));
+        assert_eq!(configured["dump"], json!(true));
+        assert_eq!(configured["index_key"], json!("user"));
+        assert_eq!(configured["cors"], json!(true));
+        assert_eq!(configured["soft_cap"], json!(1000));
+        assert_eq!(configured["request_timeout"], json!("10s"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clear_returns_the_count() -> TestResult {
+        let token_store = Arc::new(TokenStore::default());
+        let tokens = [
+            token_store
+                .create_token_full(
+                    json!({ "user": "alice" }).as_object().cloned().unwrap(),
+                    Vec::new(),
+                    None,
+                )?
+                .token,
+            token_store
+                .create_token_full(
+                    json!({ "user": "bob" }).as_object().cloned().unwrap(),
+                    Vec::new(),
+                    None,
+                )?
+                .token,
+        ];
+
+        let app = app(&["--admin-enabled"], Arc::clone(&token_store))?;
+        let (status, body) = send(app, Method::DELETE, "/admin/tokens").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "2");
+
+        assert!(token_store
+            .validate_many(&tokens)
+            .values()
+            .all(|validity| !validity.valid));
+        assert_eq!(token_store.clear(), 0);
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map as JsonObject, Value as JsonValue};
 
@@ -7,6 +10,41 @@ pub type Guid = String;
 #[derive(Deserialize)]
 pub struct CreatePayload {
     pub meta: MetaData,
+    /// either a duration or an RFC3339 timestamp, overrides the token lifetime
+    pub expires: Option<String>,
+    /// an RFC3339 timestamp, for clients that know the exact moment; not together with expires
+    pub expires_at: Option<DateTime<Utc>>,
+    /// to revoke all tokens with the same tag at once
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// an http url to POST the token and its metadata to, once it expired and is purged
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateOptions {
+    /// respond with the full token record, instead of just the token
+    #[serde(default)]
+    pub full: bool,
+}
+
+#[derive(Deserialize)]
+pub struct RekeyOptions {
+    /// the new id, generated when left out
+    pub new: Option<Guid>,
+}
+
+#[derive(Deserialize)]
+pub struct DumpOptions {
+    /// comma separated metadata fields to dump, instead of all metadata
+    pub fields: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedToken {
+    pub token: Guid,
+    pub meta: MetaData,
+    pub expires: DateTime<Utc>,
 }
 
 #[derive(Deserialize)]
@@ -15,6 +53,49 @@ pub struct UpdatePayload {
     pub meta: Option<MetaData>,
 }
 
+#[derive(Deserialize)]
+pub struct UpsertPayload {
+    pub key: String,
+    pub value: String,
+    pub meta: MetaData,
+}
+
+#[derive(Serialize)]
+pub enum UpsertResult {
+    Created(CreatedToken),
+    Updated(UpdateResponsePayload),
+}
+
+#[derive(Deserialize)]
+pub struct PatchPayload {
+    pub token: Guid,
+    pub meta: MetaData,
+}
+
+#[derive(Deserialize)]
+pub struct ExtendPayload {
+    /// duration to add to the current expiry
+    pub by: String,
+}
+
+#[derive(Deserialize)]
+pub struct ValidatePayload {
+    pub tokens: Vec<Guid>,
+}
+
+#[derive(Serialize)]
+pub struct ValidateResponse {
+    pub results: HashMap<Guid, TokenValidity>,
+}
+
+#[derive(Serialize)]
+pub struct TokenValidity {
+    pub valid: bool,
+    /// absent for an unknown token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+}
+
 #[derive(Deserialize)]
 pub struct RemovePayload {
     pub token: Guid,
@@ -25,3 +106,26 @@ pub struct UpdateResponsePayload {
     pub token: Guid,
     pub meta: MetaData,
 }
+
+/// Which optional endpoints and behaviour are enabled, for clients to discover
+#[derive(Serialize, Clone)]
+#[allow(clippy::struct_excessive_bools)] // mirrors the command line switches
+pub struct ServerConfig {
+    pub token_lifetime: String,
+    pub read_only: bool,
+    /// the /admin endpoints, only registered with --admin-enabled
+    pub admin: bool,
+    pub dump: bool,
+    pub shutdown: bool,
+    /// GET /tokens/:value with admin, with the metadata field it looks up
+    pub index_key: Option<String>,
+    /// POST /token refuses a second valid token with the same value for this metadata field
+    pub unique_key: Option<String>,
+    pub compress: bool,
+    pub cors: bool,
+    pub create_rate: Option<String>,
+    pub soft_cap: Option<usize>,
+    pub request_timeout: Option<String>,
+    /// POST /token accepts a `webhook_url`, with this many expiries queued at most
+    pub webhook_queue: Option<usize>,
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+/// Append-only record of token lifecycle events, written as JSON lines
+pub struct AuditLog {
+    /// recorded under the tokens lock, until `write_pending` writes them
+    pending: Mutex<Vec<AuditEntry>>,
+    writer: Mutex<BufWriter<File>>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditEvent {
+    Create,
+    Update,
+    Patch,
+    Extend,
+    Rekey,
+    Remove,
+    Expire,
+}
+
+#[derive(Serialize)]
+struct AuditEntry {
+    at: DateTime<Utc>,
+    event: AuditEvent,
+    token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    renewed_as: Option<String>,
+}
+
+impl AuditLog {
+    /// Open the audit log for appending, creating it when it does not exist yet
+    ///
+    /// ## Errors
+    /// when the file can not be opened for writing
+    pub fn open(path: &Path) -> io::Result<Self> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(|file| Self {
+                pending: Mutex::default(),
+                writer: Mutex::new(BufWriter::new(file)),
+            })
+    }
+
+    /// Record one event, to be written by `write_pending`;
+    /// the token ids are hashed, so the log never reveals a valid token
+    pub fn record(&self, event: AuditEvent, token: &str, renewed_as: Option<&str>) {
+        let entry = AuditEntry {
+            at: Utc::now(),
+            event,
+            token: hash_token(token),
+            renewed_as: renewed_as.map(hash_token),
+        };
+
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(entry);
+    }
+
+    /// Write the recorded events, one line each, flushed right away so no stop of the server
+    /// loses them; in the order recorded, as the events are taken under the writer lock
+    pub fn write_pending(&self) {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let pending =
+            std::mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner));
+        if pending.is_empty() {
+            return;
+        }
+
+        let written = pending
+            .iter()
+            .try_for_each(|entry| {
+                let line = serde_json::to_string(entry)?;
+                writeln!(writer, "{line}")
+            })
+            .and_then(|()| writer.flush());
+        if let Err(err) = written {
+            error!("AUDIT failed: {}", err);
+        }
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
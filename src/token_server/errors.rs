@@ -3,8 +3,6 @@
 // macro expansion, thus suppress it for this entire file
 #![allow(clippy::use_self)]
 
-use std::fmt::Display;
-
 use serde::Serialize;
 use thiserror::Error;
 
@@ -13,24 +11,69 @@ pub enum TokenUpdateFailed {
     #[error("InvalidToken")]
     InvalidToken,
 
-    #[error("InternalServerError")]
-    RwLockNotAcquired,
+    #[error("TokenInUse")]
+    TokenInUse,
+
+    #[error(transparent)]
+    NotUnique(#[from] NotUnique),
 
     #[error("Deserialize failed")]
     MustNeverOccur,
 }
 
-#[derive(Debug, Error, Serialize, Copy, Clone)]
-pub struct RwLockNotAcquired;
+#[derive(Error, Debug, Serialize)]
+pub enum CreateFailed {
+    #[error(transparent)]
+    NotUnique(#[from] NotUnique),
 
-impl Display for RwLockNotAcquired {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(if f.alternate() {
-            "could not acquire read/write lock"
-        } else {
-            "InternalServerError"
-        })
-    }
+    #[error("the soft cap of {soft_cap} tokens is reached")]
+    SoftCapReached { soft_cap: usize },
+}
+
+#[derive(Error, Debug, Serialize)]
+#[error("an active token already has '{value}' for {key}")]
+pub struct NotUnique {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Error, Debug)]
+pub enum InvalidExpiry {
+    #[error("'{input}' is neither a duration nor an RFC3339 timestamp")]
+    NotDurationOrTimestamp { input: String },
+
+    #[error("'{input}' is too far in the future")]
+    OutOfRange { input: String },
+
+    #[error("'{input}' expires later than the max token lifetime of {max} from now")]
+    TooFar { input: String, max: String },
+
+    #[error("'{input}' does not expire in the future")]
+    NotInFuture { input: String },
+
+    #[error("either expires or expires_at may be given, not both")]
+    Ambiguous,
+}
+
+#[derive(Error, Debug)]
+pub enum InvalidWebhook {
+    #[error("webhooks are not enabled, see --webhook-queue")]
+    NotEnabled,
+
+    #[error("'{url}' is not a valid url")]
+    InvalidUrl { url: String },
+
+    #[error("'{url}' is not an absolute http url")]
+    UnsupportedScheme { url: String },
+
+    #[error("'{url}' targets a loopback, private or link-local address, see --webhook-allow-host")]
+    NotPublic { url: String },
+}
+
+#[derive(Error, Debug)]
+#[error("'{input}' is not a rate like 100/1min")]
+pub struct InvalidCreateRate {
+    pub input: String,
 }
 
 // serde_josn::Error is not Serializable, as required by the #[from] attribute
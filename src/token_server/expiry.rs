@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use duration_human::DurationHuman;
+
+use super::InvalidExpiry;
+
+/// Resolve either a relative duration (`5min`) or an absolute RFC3339 timestamp
+/// (`2025-01-01T00:00:00Z`) into the moment a token expires
+///
+/// Duration parsing is tried first, so `now` is only relevant for relative input
+///
+/// ## Errors
+/// `InvalidExpiry` when the input is neither, or when the expiry is not after `now`,
+/// or later than `max_lifetime` from `now`
+pub fn resolve_expiry(
+    input: &str,
+    now: DateTime<Utc>,
+    max_lifetime: &DurationHuman,
+) -> Result<DateTime<Utc>, InvalidExpiry> {
+    let input = input.trim();
+    // a blank duration parses as zero, which is no duration asked for
+    if input.is_empty() {
+        return Err(InvalidExpiry::NotDurationOrTimestamp {
+            input: input.to_string(),
+        });
+    }
+
+    let expires = DurationHuman::parse(input).map_or_else(
+        |_| {
+            DateTime::parse_from_rfc3339(input)
+                .map(|timestamp| timestamp.with_timezone(&Utc))
+                .map_err(|_| InvalidExpiry::NotDurationOrTimestamp {
+                    input: input.to_string(),
+                })
+        },
+        |duration| {
+            chrono::Duration::from_std((&duration).into())
+                .ok()
+                .and_then(|duration| now.checked_add_signed(duration))
+                .ok_or_else(|| InvalidExpiry::OutOfRange {
+                    input: input.to_string(),
+                })
+        },
+    )?;
+
+    in_range(expires, input, now, max_lifetime)
+}
+
+/// Accept an absolute expiry, as given by a client that knows the exact moment
+///
+/// ## Errors
+/// `InvalidExpiry::NotInFuture` when the expiry is not after `now`,
+/// `InvalidExpiry::TooFar` when it is later than `max_lifetime` from `now`
+pub fn check_expires_at(
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    max_lifetime: &DurationHuman,
+) -> Result<DateTime<Utc>, InvalidExpiry> {
+    in_range(expires_at, &expires_at.to_rfc3339(), now, max_lifetime)
+}
+
+fn in_range(
+    expires: DateTime<Utc>,
+    input: &str,
+    now: DateTime<Utc>,
+    max_lifetime: &DurationHuman,
+) -> Result<DateTime<Utc>, InvalidExpiry> {
+    let latest = chrono::Duration::from_std(max_lifetime.into())
+        .ok()
+        .and_then(|max_lifetime| now.checked_add_signed(max_lifetime));
+
+    if expires <= now {
+        Err(InvalidExpiry::NotInFuture {
+            input: input.to_string(),
+        })
+    } else if latest.is_some_and(|latest| expires > latest) {
+        Err(InvalidExpiry::TooFar {
+            input: input.to_string(),
+            max: max_lifetime.to_string(),
+        })
+    } else {
+        Ok(expires)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_in_result, clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use std::error::Error;
+
+    use chrono::{DateTime, TimeZone, Utc};
+    use duration_human::DurationHuman;
+
+    use super::{check_expires_at, resolve_expiry, InvalidExpiry};
+
+    type TestResult = Result<(), Box<dyn Error>>;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn max_lifetime() -> Result<DurationHuman, Box<dyn Error>> {
+        Ok(DurationHuman::parse("60day")?)
+    }
+
+    #[test]
+    fn resolves_a_duration() -> TestResult {
+        let expires = resolve_expiry(" 5min ", now(), &max_lifetime()?)?;
+        assert_eq!(expires, now() + chrono::Duration::minutes(5));
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_a_timestamp() -> TestResult {
+        let expires = resolve_expiry("2024-06-02T00:00:00Z", now(), &max_lifetime()?)?;
+        assert_eq!(expires, Utc.with_ymd_and_hms(2024, 6, 2, 0, 0, 0).unwrap());
+
+        let expires = resolve_expiry("2024-06-01T14:00:00+02:00", now(), &max_lifetime()?);
+        assert!(matches!(expires, Err(InvalidExpiry::NotInFuture { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_input() -> TestResult {
+        for input in ["", "soon", "5 lightyears", "2024-13-01T00:00:00Z"] {
+            assert!(
+                matches!(
+                    resolve_expiry(input, now(), &max_lifetime()?),
+                    Err(InvalidExpiry::NotDurationOrTimestamp { .. })
+                ),
+                "{input}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_an_expiry_beyond_the_max_lifetime() -> TestResult {
+        for input in ["61day", "2024-08-01T12:00:00Z", "100year"] {
+            assert!(
+                matches!(
+                    resolve_expiry(input, now(), &max_lifetime()?),
+                    Err(InvalidExpiry::TooFar { .. })
+                ),
+                "{input}"
+            );
+        }
+        assert!(resolve_expiry("60day", now(), &max_lifetime()?).is_ok());
+
+        let expires_at = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        assert!(matches!(
+            check_expires_at(expires_at, now(), &max_lifetime()?),
+            Err(InvalidExpiry::TooFar { .. })
+        ));
+        Ok(())
+    }
+}
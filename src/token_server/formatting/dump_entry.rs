@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Serializer};
 
@@ -5,20 +7,35 @@ use crate::token_server::api::MetaData;
 
 #[derive(Serialize)]
 pub struct DumpEntry<'de> {
-    #[serde(serialize_with = "format_expiration")]
+    #[serde(serialize_with = "format_timestamp")]
+    created: DateTime<Utc>,
+    #[serde(serialize_with = "format_timestamp")]
     expires: DateTime<Utc>,
-    meta: &'de MetaData,
+    meta: Cow<'de, MetaData>,
 }
 
 impl<'de> DumpEntry<'de> {
-    pub const fn new(expires: DateTime<Utc>, meta: &'de MetaData) -> Self {
-        Self { expires, meta }
+    pub const fn new(
+        created: DateTime<Utc>,
+        expires: DateTime<Utc>,
+        meta: Cow<'de, MetaData>,
+    ) -> Self {
+        Self {
+            created,
+            expires,
+            meta,
+        }
     }
 }
 
-fn format_expiration<S>(expires: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error>
+fn format_timestamp<S>(timestamp: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    s.serialize_str(expires.format("%Y-%m-%d %H:%M:%S").to_string().as_str())
+    s.serialize_str(
+        timestamp
+            .format("%Y-%m-%d %H:%M:%S%.3f")
+            .to_string()
+            .as_str(),
+    )
 }
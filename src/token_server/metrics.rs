@@ -0,0 +1,128 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Why a request was rejected
+#[derive(Clone, Copy)]
+pub enum Rejection {
+    /// a malformed body, or an invalid webhook url
+    BadInput,
+    InvalidExpiry,
+    InvalidToken,
+    Overloaded,
+    TimedOut,
+    RateLimited,
+}
+
+impl Rejection {
+    const ALL: [Self; 6] = [
+        Self::BadInput,
+        Self::InvalidExpiry,
+        Self::InvalidToken,
+        Self::Overloaded,
+        Self::TimedOut,
+        Self::RateLimited,
+    ];
+
+    const fn reason(self) -> &'static str {
+        match self {
+            Self::BadInput => "bad_input",
+            Self::InvalidExpiry => "invalid_expiry",
+            Self::InvalidToken => "invalid_token",
+            Self::Overloaded => "overloaded",
+            Self::TimedOut => "timed_out",
+            Self::RateLimited => "rate_limited",
+        }
+    }
+}
+
+/// Counters, formatted for Prometheus by `Display`
+#[derive(Default)]
+pub struct Metrics {
+    rejected: [AtomicU64; Rejection::ALL.len()],
+    tokens_high_water_mark: AtomicUsize,
+    lifetimes_observed: AtomicU64,
+    lifetime_millis_total: AtomicU64,
+}
+
+impl Metrics {
+    pub fn reject(&self, rejection: Rejection) {
+        self.rejected[rejection as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rejected(&self, rejection: Rejection) -> u64 {
+        self.rejected[rejection as usize].load(Ordering::Relaxed)
+    }
+
+    /// Raise the high-water mark to this token count; true when that is a new maximum
+    pub fn record_token_count(&self, count: usize) -> bool {
+        self.tokens_high_water_mark
+            .fetch_max(count, Ordering::Relaxed)
+            < count
+    }
+
+    pub fn tokens_high_water_mark(&self) -> usize {
+        self.tokens_high_water_mark.load(Ordering::Relaxed)
+    }
+
+    /// Count how long a token actually lived, until it expired, was removed or renewed;
+    /// in milliseconds, so the total lasts for billions of tokens
+    pub fn observe_lifetime(&self, lifetime: Duration) {
+        let millis = u64::try_from(lifetime.as_millis()).unwrap_or(u64::MAX);
+
+        self.lifetime_millis_total
+            .fetch_add(millis, Ordering::Relaxed);
+        self.lifetimes_observed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "# HELP token_server_rejected_requests_total Requests rejected for bad input, overload, an invalid expiry, an invalid token, a timeout or the create rate limit"
+        )?;
+        writeln!(f, "# TYPE token_server_rejected_requests_total counter")?;
+        for rejection in Rejection::ALL {
+            writeln!(
+                f,
+                "token_server_rejected_requests_total{{reason=\"{reason}\"}} {count}",
+                reason = rejection.reason(),
+                count = self.rejected(rejection)
+            )?;
+        }
+
+        writeln!(
+            f,
+            "# HELP token_server_tokens_high_water_mark Highest number of tokens held at once"
+        )?;
+        writeln!(f, "# TYPE token_server_tokens_high_water_mark gauge")?;
+        writeln!(
+            f,
+            "token_server_tokens_high_water_mark {}",
+            self.tokens_high_water_mark()
+        )?;
+
+        let lifetime_millis_total = self.lifetime_millis_total.load(Ordering::Relaxed);
+        writeln!(
+            f,
+            "# HELP token_server_token_lifetime_seconds How long tokens lived until they expired, were removed or renewed; sum / count is the average"
+        )?;
+        writeln!(f, "# TYPE token_server_token_lifetime_seconds summary")?;
+        writeln!(
+            f,
+            "token_server_token_lifetime_seconds_sum {seconds}.{millis:03}",
+            seconds = lifetime_millis_total / 1000,
+            millis = lifetime_millis_total % 1000
+        )?;
+        writeln!(
+            f,
+            "token_server_token_lifetime_seconds_count {}",
+            self.lifetimes_observed.load(Ordering::Relaxed)
+        )?;
+
+        Ok(())
+    }
+}
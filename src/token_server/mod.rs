@@ -2,10 +2,29 @@ pub mod api;
 pub mod routes;
 
 mod formatting;
+mod tags;
 
 mod errors;
 
 pub use errors::*;
 
+mod audit_log;
+pub use audit_log::*;
+
+mod metrics;
+pub use metrics::*;
+
+mod rate_limit;
+pub use rate_limit::*;
+
+mod expiry;
+pub use expiry::*;
+
 mod token_store;
 pub use token_store::*;
+
+mod unix_socket;
+pub use unix_socket::*;
+
+mod webhook;
+pub use webhook::*;
@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration as StdDuration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use duration_human::DurationHuman;
+use http::{header::RETRY_AFTER, Request, StatusCode};
+
+use super::{InvalidCreateRate, Rejection, TokenStore};
+
+/// At most `limit` tokens created per `window` by one client, e.g. "100/1min"
+#[derive(Clone, Copy)]
+pub struct CreateRate {
+    limit: NonZeroU32,
+    window: DurationHuman,
+}
+
+impl CreateRate {
+    /// Parse the rate as the limit and the window, separated by a slash
+    ///
+    /// ## Errors
+    /// when the limit is not a positive integer or the window not a positive duration
+    pub fn parse(rate: &str) -> Result<Self, InvalidCreateRate> {
+        let invalid = || InvalidCreateRate {
+            input: rate.to_string(),
+        };
+
+        let (limit, window) = rate.split_once('/').ok_or_else(invalid)?;
+        let limit = limit.trim().parse::<NonZeroU32>().map_err(|_| invalid())?;
+        let window = DurationHuman::parse(window.trim()).map_err(|_| invalid())?;
+
+        if StdDuration::from(&window).is_zero() {
+            Err(invalid())
+        } else {
+            Ok(Self { limit, window })
+        }
+    }
+}
+
+impl Display for CreateRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.limit, self.window)
+    }
+}
+
+/// A token bucket per client IP, which refills at the create rate
+pub struct RateLimiter {
+    rate: CreateRate,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    available: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: CreateRate) -> Self {
+        Self {
+            rate,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Take one create from the bucket of this client, or tell how long until one is available
+    ///
+    /// ## Errors
+    /// the time to wait, when the bucket of this client is empty
+    #[allow(clippy::significant_drop_tightening)] // the bucket is updated under the lock
+    pub fn acquire(&self, client: IpAddr) -> Result<(), StdDuration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let bucket = buckets.entry(client).or_insert_with(|| Bucket {
+            available: self.capacity(),
+            updated: now,
+        });
+
+        bucket.available = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        if bucket.available >= 1.0 {
+            bucket.available -= 1.0;
+            Ok(())
+        } else {
+            Err(StdDuration::from_secs_f64(
+                (1.0 - bucket.available) / self.per_second(),
+            ))
+        }
+    }
+
+    /// Forget the clients of which the bucket has refilled completely
+    pub fn forget_idle(&self) {
+        let now = Instant::now();
+        self.buckets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|_client, bucket| self.refilled(bucket, now) < self.capacity());
+    }
+
+    fn capacity(&self) -> f64 {
+        f64::from(self.rate.limit.get())
+    }
+
+    fn per_second(&self) -> f64 {
+        self.capacity() / StdDuration::from(&self.rate.window).as_secs_f64()
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        now.duration_since(bucket.updated)
+            .as_secs_f64()
+            .mul_add(self.per_second(), bucket.available)
+            .min(self.capacity())
+    }
+}
+
+/// Middleware that responds with 429 Too Many Requests when the client exceeds the create rate,
+/// counting the rejection in the metrics of the token store
+pub async fn limit_create_rate<B>(
+    State((limiter, token_store)): State<(Arc<RateLimiter>, Arc<TokenStore>)>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    match limiter.acquire(peer.ip()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            // whole seconds, rounded up
+            let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            token_store.metrics().reject(Rejection::RateLimited);
+
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.to_string())],
+                "TooManyRequests",
+            )
+                .into_response()
+        }
+    }
+}
@@ -1,60 +1,260 @@
 #![allow(clippy::unused_async)]
-use std::sync::Arc;
+use std::{collections::HashSet, fmt::Display, sync::Arc};
 
 use axum::{
-    extract::{self, State},
+    async_trait,
+    body::Body,
+    extract::{self, FromRequest, Path, State},
     response::{IntoResponse, Response},
     Json,
 };
-use http::StatusCode;
+use chrono::Utc;
+use duration_human::DurationHuman;
+use http::{header::CONTENT_TYPE, Request, StatusCode, Uri};
+use serde::de::DeserializeOwned;
+use serde_json::json;
 
 use tracing::error;
 
 use super::{
-    api::{CreatePayload, RemovePayload, UpdatePayload},
-    RwLockNotAcquired, TokenStore, TokenUpdateFailed,
+    api::{
+        CreateOptions, CreatePayload, DumpOptions, ExtendPayload, PatchPayload, RekeyOptions,
+        RemovePayload, UpdatePayload, UpsertPayload, ValidatePayload, ValidateResponse,
+    },
+    check_expires_at, resolve_expiry, CreateFailed, InvalidExpiry, Rejection, TokenStore,
+    TokenUpdateFailed,
 };
 
+/// The `Json` extractor, counting a body that is malformed or has the wrong
+/// content type as bad input
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T> FromRequest<Arc<TokenStore>, Body> for JsonBody<T>
+where
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(
+        request: Request<Body>,
+        token_store: &Arc<TokenStore>,
+    ) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(request, token_store).await {
+            Ok(Json(payload)) => Ok(Self(payload)),
+            Err(rejection) => {
+                token_store.metrics().reject(Rejection::BadInput);
+                Err(rejection.into_response())
+            }
+        }
+    }
+}
+
 pub async fn create_token(
     extract::State(token_store): State<Arc<TokenStore>>,
-    extract::Json(metadata): extract::Json<CreatePayload>,
-) -> (StatusCode, String) {
-    token_store.create_token(metadata.meta).map_or_else(
-        |_err| {
-            ResponseFromResult::internal_server_error()
-                .log()
-                .into_tuple()
-        },
-        |token| (StatusCode::OK, token),
-    )
+    extract::Query(options): extract::Query<CreateOptions>,
+    JsonBody(metadata): JsonBody<CreatePayload>,
+) -> Response {
+    let now = Utc::now();
+    let expires = match (metadata.expires.as_deref(), metadata.expires_at) {
+        (Some(_), Some(_)) => Err(InvalidExpiry::Ambiguous),
+        (Some(expires), None) => resolve_expiry(expires, now, token_store.max_lifetime()).map(Some),
+        (None, Some(expires_at)) => {
+            check_expires_at(expires_at, now, token_store.max_lifetime()).map(Some)
+        }
+        (None, None) => Ok(None),
+    };
+
+    let expires = match expires {
+        Ok(expires) => expires,
+        Err(err) => {
+            token_store.metrics().reject(Rejection::InvalidExpiry);
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+    };
+
+    let webhook = metadata
+        .webhook_url
+        .as_deref()
+        .map(|url| token_store.parse_webhook_url(url))
+        .transpose();
+
+    let webhook = match webhook {
+        Ok(webhook) => webhook,
+        Err(err) => {
+            token_store.metrics().reject(Rejection::BadInput);
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+    };
+
+    let created = match expires {
+        Some(expires) => {
+            token_store.create_token_expiring_at(metadata.meta, metadata.tags, webhook, expires)
+        }
+        None => token_store.create_token_full(metadata.meta, metadata.tags, webhook),
+    };
+
+    match created {
+        Ok(created) => {
+            if options.full {
+                Json(created).into_response()
+            } else {
+                Json(json!({ "token": created.token })).into_response()
+            }
+        }
+        Err(err) => create_failed(&token_store, err),
+    }
+}
+
+pub async fn upsert_token(
+    State(token_store): State<Arc<TokenStore>>,
+    JsonBody(payload): JsonBody<UpsertPayload>,
+) -> Response {
+    match token_store.upsert_by(&payload.key, &payload.value, payload.meta) {
+        Ok(upserted) => Json(upserted).into_response(),
+        Err(err) => create_failed(&token_store, err),
+    }
+}
+
+/// Respond with 409 Conflict, or 503 Service Unavailable at the soft cap
+fn create_failed(token_store: &TokenStore, err: CreateFailed) -> Response {
+    let status = match err {
+        CreateFailed::NotUnique(_) => StatusCode::CONFLICT,
+        CreateFailed::SoftCapReached { .. } => {
+            token_store.metrics().reject(Rejection::Overloaded);
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+
+    (status, Json(Err::<(), _>(err))).into_response()
 }
 
 pub async fn update_token(
     State(token_store): State<Arc<TokenStore>>,
-    extract::Json(payload): extract::Json<UpdatePayload>,
+    JsonBody(payload): JsonBody<UpdatePayload>,
 ) -> Response {
     let update_result = token_store.update_token(&payload.token, payload.meta);
 
     match update_result {
-        Err(TokenUpdateFailed::RwLockNotAcquired) => ResponseFromResult::internal_server_error()
-            .log()
-            .into_response(),
+        Err(TokenUpdateFailed::InvalidToken) => {
+            token_store.metrics().reject(Rejection::InvalidToken);
+            Json(update_result).into_response()
+        }
+        Err(TokenUpdateFailed::NotUnique(_)) => {
+            (StatusCode::CONFLICT, Json(update_result)).into_response()
+        }
         _ => Json(update_result).into_response(),
     }
 }
 
+pub async fn patch_token(
+    State(token_store): State<Arc<TokenStore>>,
+    JsonBody(payload): JsonBody<PatchPayload>,
+) -> Response {
+    let patch_result = token_store.patch_metadata(&payload.token, payload.meta);
+
+    match patch_result {
+        Err(TokenUpdateFailed::InvalidToken) => {
+            token_store.metrics().reject(Rejection::InvalidToken);
+            Json(patch_result).into_response()
+        }
+        Err(TokenUpdateFailed::NotUnique(_)) => {
+            (StatusCode::CONFLICT, Json(patch_result)).into_response()
+        }
+        _ => Json(patch_result).into_response(),
+    }
+}
+
+pub async fn extend_token(
+    State(token_store): State<Arc<TokenStore>>,
+    Path(token): Path<String>,
+    JsonBody(payload): JsonBody<ExtendPayload>,
+) -> Response {
+    let by = match DurationHuman::parse(&payload.by) {
+        Ok(by) => by,
+        Err(err) => {
+            token_store.metrics().reject(Rejection::InvalidExpiry);
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+    };
+
+    let extend_result = token_store.extend_token(&token, by);
+
+    match extend_result {
+        Err(TokenUpdateFailed::InvalidToken) => {
+            token_store.metrics().reject(Rejection::InvalidToken);
+            (StatusCode::NOT_FOUND, Json(extend_result)).into_response()
+        }
+        _ => Json(extend_result).into_response(),
+    }
+}
+
+pub async fn rekey_token(
+    State(token_store): State<Arc<TokenStore>>,
+    Path(token): Path<String>,
+    extract::Query(options): extract::Query<RekeyOptions>,
+) -> Response {
+    let rekey_result = token_store.rekey(&token, options.new);
+
+    match rekey_result {
+        Err(TokenUpdateFailed::InvalidToken) => {
+            token_store.metrics().reject(Rejection::InvalidToken);
+            (StatusCode::NOT_FOUND, Json(rekey_result)).into_response()
+        }
+        Err(TokenUpdateFailed::TokenInUse) => {
+            (StatusCode::CONFLICT, Json(rekey_result)).into_response()
+        }
+        _ => Json(rekey_result).into_response(),
+    }
+}
+
 pub async fn remove_token(
     State(token_store): State<Arc<TokenStore>>,
-    extract::Json(payload): extract::Json<RemovePayload>,
+    JsonBody(payload): JsonBody<RemovePayload>,
 ) -> Response {
-    token_store.remove_token(&payload.token).map_or_else(
-        |_e| {
-            ResponseFromResult::internal_server_error()
-                .log()
-                .into_response()
-        },
-        |()| StatusCode::ACCEPTED.into_response(),
+    token_store.remove_token(&payload.token);
+
+    StatusCode::ACCEPTED.into_response()
+}
+
+pub async fn remove_tokens_by_tag(
+    State(token_store): State<Arc<TokenStore>>,
+    Path(tag): Path<String>,
+) -> Response {
+    Json(token_store.remove_by_tag(&tag)).into_response()
+}
+
+pub async fn clear_tokens(State(token_store): State<Arc<TokenStore>>) -> Response {
+    Json(token_store.clear()).into_response()
+}
+
+pub async fn validate_tokens(
+    State(token_store): State<Arc<TokenStore>>,
+    JsonBody(payload): JsonBody<ValidatePayload>,
+) -> Response {
+    let results = token_store.validate_many(&payload.tokens);
+
+    Json(ValidateResponse { results }).into_response()
+}
+
+pub async fn find_tokens(
+    State(token_store): State<Arc<TokenStore>>,
+    Path(key_value): Path<String>,
+) -> Response {
+    Json(token_store.find_by(&key_value)).into_response()
+}
+
+pub async fn show_metrics(State(token_store): State<Arc<TokenStore>>) -> String {
+    token_store.metrics().to_string()
+}
+
+/// Unknown routes respond with a JSON body, like the other errors
+pub async fn not_found(uri: Uri) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "not_found", "path": uri.path() })),
     )
+        .into_response()
 }
 
 pub async fn dump_meta(State(token_store): State<Arc<TokenStore>>) -> StatusCode {
@@ -63,6 +263,25 @@ pub async fn dump_meta(State(token_store): State<Arc<TokenStore>>) -> StatusCode
     StatusCode::ACCEPTED
 }
 
+pub async fn dump_report(
+    State(token_store): State<Arc<TokenStore>>,
+    extract::Query(options): extract::Query<DumpOptions>,
+) -> Response {
+    let fields = options
+        .fields
+        .as_deref()
+        .map(|fields| fields.split(',').map(str::trim).collect::<HashSet<_>>());
+
+    token_store.dump_report(fields.as_ref()).map_or_else(
+        |err| {
+            ResponseFromResult::internal_server_error(&err)
+                .log()
+                .into_response()
+        },
+        |report| ([(CONTENT_TYPE, "application/json")], report).into_response(),
+    )
+}
+
 pub async fn shutdown_server(extract::State(token_store): State<Arc<TokenStore>>) -> StatusCode {
     token_store.shutdown();
     StatusCode::ACCEPTED
@@ -76,11 +295,11 @@ struct ResponseFromResult {
 
 impl ResponseFromResult {
     /// respond with 500 Internal server error and write error message to the server log
-    fn internal_server_error() -> Self {
+    fn internal_server_error(err: &impl Display) -> Self {
         Self {
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            status_text: format!("{RwLockNotAcquired}",),
-            log_message: format!("{RwLockNotAcquired:#}"),
+            status_text: String::from("InternalServerError"),
+            log_message: format!("Internal server error: {err}"),
         }
     }
 
@@ -0,0 +1,55 @@
+use std::collections::{HashMap, HashSet};
+
+use super::api::Guid;
+
+/// The tags of each token, and the tokens of each tag to revoke them at once
+#[derive(Default)]
+pub struct TagIndex {
+    by_token: HashMap<Guid, HashSet<String>>,
+    by_tag: HashMap<String, HashSet<Guid>>,
+}
+
+impl TagIndex {
+    pub fn insert(&mut self, token: &str, tags: impl IntoIterator<Item = String>) {
+        let tags = tags.into_iter().collect::<HashSet<String>>();
+        if tags.is_empty() {
+            return;
+        }
+
+        for tag in &tags {
+            self.by_tag
+                .entry(tag.clone())
+                .or_default()
+                .insert(token.to_string());
+        }
+        self.by_token
+            .entry(token.to_string())
+            .or_default()
+            .extend(tags);
+    }
+
+    /// Forget the token, returning its tags
+    pub fn remove(&mut self, token: &str) -> HashSet<String> {
+        let tags = self.by_token.remove(token).unwrap_or_default();
+        for tag in &tags {
+            if let Some(tokens) = self.by_tag.get_mut(tag) {
+                tokens.remove(token);
+                if tokens.is_empty() {
+                    self.by_tag.remove(tag);
+                }
+            }
+        }
+
+        tags
+    }
+
+    /// Forget all tokens that carry this tag, returning those tokens
+    pub fn remove_tag(&mut self, tag: &str) -> HashSet<Guid> {
+        let tokens = self.by_tag.get(tag).cloned().unwrap_or_default();
+        for token in &tokens {
+            self.remove(token);
+        }
+
+        tokens
+    }
+}
@@ -1,27 +1,73 @@
-use std::{collections::HashMap, sync::RwLock, time::Instant};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    ops::{Deref, DerefMut},
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration as StdDuration, Instant},
+};
 
 use chrono::{DateTime, Utc};
 use duration_human::DurationHuman;
+use http::Uri;
+use serde_json::Value as JsonValue;
 
 use axum_server::Handle;
-use tracing::debug;
+use tokio::sync::Notify;
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use super::{
-    api::{Guid, MetaData, UpdateResponsePayload},
+    api::{CreatedToken, Guid, MetaData, TokenValidity, UpdateResponsePayload, UpsertResult},
     formatting::{DumpEntry, PurgeResult},
-    RwLockNotAcquired, TokenUpdateFailed,
+    tags::TagIndex,
+    AuditEvent, AuditLog, CreateFailed, InvalidWebhook, Metrics, NotUnique, TokenUpdateFailed,
+    Webhooks,
 };
 
 pub struct TokenStore {
     tokens: RwLock<TokensByID>,
     handle: Option<Handle>,
+    shutdown_notify: Notify,
     started_at_instant: Instant,
     started_at_utc: DateTime<Utc>,
     token_lifetime: DurationHuman,
+    min_lifetime: DurationHuman,
+    max_lifetime: DurationHuman,
+    grace: DurationHuman,
+    max_clock_drift: DurationHuman,
+    soft_cap: Option<NonZeroUsize>,
+    dump_pretty: bool,
+    audit_log: Option<AuditLog>,
+    webhooks: Option<Webhooks>,
+    index_key: Option<String>,
+    unique_key: Option<String>,
+    index: RwLock<TokensByIndexValue>,
+    tags: RwLock<TagIndex>,
+    metrics: Metrics,
+}
+
+type TokensByID = HashMap<Guid, TokenEntry>;
+
+/// What is held for each token
+struct TokenEntry {
+    expires: Instant,
+    meta: MetaData,
+    created: Instant,
 }
 
-type TokensByID = HashMap<Guid, (Instant, MetaData)>;
+/// Secondary index, only maintained while holding the write lock on the tokens
+type TokensByIndexValue = HashMap<String, HashSet<Guid>>;
+
+/// The write lock on the tokens; the audit events recorded under it are written once
+/// it is released, so no request waits for the audit log while holding the lock
+struct TokensWriteGuard<'a> {
+    // released first, as fields are dropped in the order they are declared
+    tokens: RwLockWriteGuard<'a, TokensByID>,
+    _audit: WritePendingAudit<'a>,
+}
+
+struct WritePendingAudit<'a>(Option<&'a AuditLog>);
 
 impl TokenStore {
     pub const fn with_token_lifetime(mut self, lifetime: DurationHuman) -> Self {
@@ -30,6 +76,52 @@ impl TokenStore {
         self
     }
 
+    /// Tokens with an explicit expiry live at least this long; an earlier expiry is
+    /// clamped to the floor rather than rejected
+    pub const fn with_min_lifetime(mut self, min_lifetime: DurationHuman) -> Self {
+        self.min_lifetime = min_lifetime;
+
+        self
+    }
+
+    /// Extending a token, or creating one with an explicit expiry, never postpones
+    /// its expiry further than this from now
+    pub const fn with_max_lifetime(mut self, max_lifetime: DurationHuman) -> Self {
+        self.max_lifetime = max_lifetime;
+
+        self
+    }
+
+    /// Tokens remain valid this long past their expiry, to tolerate clock skew and requests
+    /// in flight; the purge still removes them at their expiry, so a token is only valid
+    /// during the grace period until the next purge
+    pub const fn with_grace(mut self, grace: DurationHuman) -> Self {
+        self.grace = grace;
+
+        self
+    }
+
+    /// Warn when the monotonic and wall clock drifted apart further than this since startup
+    pub const fn with_max_clock_drift(mut self, max_clock_drift: DurationHuman) -> Self {
+        self.max_clock_drift = max_clock_drift;
+
+        self
+    }
+
+    /// Refuse to create tokens once this many are held, while existing tokens remain usable
+    pub const fn with_soft_cap(mut self, soft_cap: Option<NonZeroUsize>) -> Self {
+        self.soft_cap = soft_cap;
+
+        self
+    }
+
+    /// Dump the metadata as indented JSON, instead of compact
+    pub const fn with_dump_pretty(mut self, dump_pretty: bool) -> Self {
+        self.dump_pretty = dump_pretty;
+
+        self
+    }
+
     #[allow(clippy::missing_const_for_fn)]
     pub fn with_handle(mut self, handle: Handle) -> Self {
         self.handle = Some(handle);
@@ -37,26 +129,192 @@ impl TokenStore {
         self
     }
 
-    pub fn create_token(&self, metadata: MetaData) -> Result<String, RwLockNotAcquired> {
-        self.tokens
-            .write()
-            .or(Err(RwLockNotAcquired))
-            .map(|mut tokens| {
-                let (token, expires) = self.new_token();
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn with_audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+        self.audit_log = audit_log;
+
+        self
+    }
 
-                tokens.insert(token.clone(), (expires, metadata));
+    /// Accept a webhook per token, to POST to once the purge removed it expired
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn with_webhooks(mut self, webhooks: Option<Webhooks>) -> Self {
+        self.webhooks = webhooks;
 
-                token
-            })
+        self
     }
 
-    pub fn remove_token(&self, token: &String) -> Result<(), RwLockNotAcquired> {
-        self.tokens
-            .write()
-            .or(Err(RwLockNotAcquired))
-            .map(|mut tokens| {
-                tokens.remove(token);
-            })
+    /// Maintain an index on the value of this metadata field, to find tokens by that value
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn with_index_key(mut self, index_key: Option<String>) -> Self {
+        self.index_key = index_key;
+
+        self
+    }
+
+    /// Refuse to create a token when a valid token has the same value for this metadata field
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn with_unique_key(mut self, unique_key: Option<String>) -> Self {
+        self.unique_key = unique_key;
+
+        self
+    }
+
+    pub const fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub const fn max_lifetime(&self) -> &DurationHuman {
+        &self.max_lifetime
+    }
+
+    /// Check the url of a webhook, to POST to once the token expired
+    ///
+    /// ## Errors
+    /// `InvalidWebhook::NotEnabled` without webhooks, else as `Webhooks::parse_url`
+    pub fn parse_webhook_url(&self, url: &str) -> Result<Uri, InvalidWebhook> {
+        self.webhooks
+            .as_ref()
+            .ok_or(InvalidWebhook::NotEnabled)?
+            .parse_url(url)
+    }
+
+    /// Create a token, returning the token together with its metadata and expiry
+    pub fn create_token_full(
+        &self,
+        metadata: MetaData,
+        tags: Vec<String>,
+        webhook: Option<Uri>,
+    ) -> Result<CreatedToken, CreateFailed> {
+        let mut tokens = self.write_tokens();
+        self.check_soft_cap(&tokens)?;
+        self.check_unique(&tokens, &metadata, None)?;
+
+        Ok(self.insert_token(&mut tokens, metadata, tags, webhook, None))
+    }
+
+    /// Merge the metadata into the valid token of which the metadata `key` has this `value`,
+    /// or create a new token for it; both under the same lock, so no duplicates are created
+    #[allow(clippy::significant_drop_tightening)] // the lock also covers the index and tags
+    pub fn upsert_by(
+        &self,
+        key: &str,
+        value: &str,
+        mut metadata: MetaData,
+    ) -> Result<UpsertResult, CreateFailed> {
+        let mut tokens = self.write_tokens();
+
+        if let Some(token) = self.find_valid_by(&tokens, key, value) {
+            if let Some(TokenEntry { meta, .. }) = tokens.get(&token) {
+                let mut merged = meta.clone();
+                merged.extend(metadata);
+                self.check_unique(&tokens, &merged, Some(&token))?;
+
+                self.index_remove(&token, meta);
+                self.index_insert(&token, &merged);
+                if let Some(entry) = tokens.get_mut(&token) {
+                    entry.meta.clone_from(&merged);
+                }
+                self.audit(AuditEvent::Patch, &token, None);
+
+                return Ok(UpsertResult::Updated(UpdateResponsePayload {
+                    token,
+                    meta: merged,
+                }));
+            }
+        }
+
+        metadata
+            .entry(key)
+            .or_insert_with(|| JsonValue::String(value.to_string()));
+        self.check_soft_cap(&tokens)?;
+        self.check_unique(&tokens, &metadata, None)?;
+
+        Ok(UpsertResult::Created(self.insert_token(
+            &mut tokens,
+            metadata,
+            Vec::new(),
+            None,
+            None,
+        )))
+    }
+
+    /// Create a token that expires at the given moment, rather than after the token lifetime
+    ///
+    /// An expiry sooner than the minimal lifetime is postponed to that minimum
+    pub fn create_token_expiring_at(
+        &self,
+        metadata: MetaData,
+        tags: Vec<String>,
+        webhook: Option<Uri>,
+        expires: DateTime<Utc>,
+    ) -> Result<CreatedToken, CreateFailed> {
+        let remaining = DurationHuman::between(Utc::now(), expires)
+            .unwrap_or_else(|_| DurationHuman::new(0))
+            .max(self.min_lifetime);
+
+        let mut tokens = self.write_tokens();
+        self.check_soft_cap(&tokens)?;
+        self.check_unique(&tokens, &metadata, None)?;
+
+        Ok(self.insert_token(
+            &mut tokens,
+            metadata,
+            tags,
+            webhook,
+            Some(remaining + Instant::now()),
+        ))
+    }
+
+    pub fn remove_token(&self, token: &String) {
+        let mut tokens = self.write_tokens();
+
+        if let Some(TokenEntry { meta, created, .. }) = tokens.remove(token) {
+            self.metrics.observe_lifetime(created.elapsed());
+            self.index_remove(token, &meta);
+            self.write_tags().remove(token);
+            self.webhook_remove(token);
+            self.audit(AuditEvent::Remove, token, None);
+        }
+    }
+
+    /// Remove all tokens that carry this tag, returning how many were removed
+    pub fn remove_by_tag(&self, tag: &str) -> usize {
+        let mut tokens = self.write_tokens();
+        let tagged = self.write_tags().remove_tag(tag);
+
+        let mut removed = 0;
+        for token in tagged {
+            if let Some(TokenEntry { meta, created, .. }) = tokens.remove(&token) {
+                self.metrics.observe_lifetime(created.elapsed());
+                self.index_remove(&token, &meta);
+                self.webhook_remove(&token);
+                self.audit(AuditEvent::Remove, &token, None);
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+
+    /// Remove all tokens, returning how many were removed
+    pub fn clear(&self) -> usize {
+        let mut tokens = self.write_tokens();
+
+        for (token, TokenEntry { created, .. }) in &*tokens {
+            self.metrics.observe_lifetime(created.elapsed());
+            self.audit(AuditEvent::Remove, token, None);
+        }
+        self.write_index().clear();
+        *self.write_tags() = TagIndex::default();
+        if let Some(ref webhooks) = self.webhooks {
+            webhooks.clear();
+        }
+
+        let removed = tokens.len();
+        tokens.clear();
+
+        removed
     }
 
     pub fn update_token(
@@ -64,68 +322,276 @@ impl TokenStore {
         tokenkey: &String,
         metadata_update: Option<MetaData>,
     ) -> Result<UpdateResponsePayload, TokenUpdateFailed> {
-        self.tokens
-            .write()
-            .or(Err(TokenUpdateFailed::RwLockNotAcquired))
-            .and_then(|mut tokens| {
-                tokens
-                    .remove(tokenkey)
-                    .and_then(|(expires, mut meta)| {
-                        if expires > Instant::now() {
-                            let (token, expires) = self.new_token();
-
-                            if let Some(metadata_update) = metadata_update {
-                                meta.extend(metadata_update);
-                            }
-
-                            tokens.insert(token.clone(), (expires, meta.clone()));
-                            Some(UpdateResponsePayload { token, meta })
-                        } else {
-                            None
+        let mut tokens = self.write_tokens();
+
+        // the renewed token must not take a unique value of another token, checked before
+        // the token is removed, so it is kept on a conflict
+        if let (Some(entry), Some(update)) = (tokens.get(tokenkey), metadata_update.as_ref()) {
+            if self.is_valid(entry.expires, Instant::now()) {
+                let mut meta = entry.meta.clone();
+                meta.extend(update.clone());
+                self.check_unique(&tokens, &meta, Some(tokenkey))?;
+            }
+        }
+
+        tokens
+            .remove(tokenkey)
+            .and_then(
+                |TokenEntry {
+                     expires,
+                     mut meta,
+                     created,
+                 }| {
+                    self.index_remove(tokenkey, &meta);
+                    let tags = self.write_tags().remove(tokenkey);
+
+                    if self.is_valid(expires, Instant::now()) {
+                        self.metrics.observe_lifetime(created.elapsed());
+                        let (token, expires) = self.new_token();
+
+                        if let Some(metadata_update) = metadata_update {
+                            meta.extend(metadata_update);
                         }
-                    })
-                    .ok_or(TokenUpdateFailed::InvalidToken)
+
+                        tokens.insert(
+                            token.clone(),
+                            TokenEntry {
+                                expires,
+                                meta: meta.clone(),
+                                created: Instant::now(),
+                            },
+                        );
+                        self.index_insert(&token, &meta);
+                        self.write_tags().insert(&token, tags);
+                        self.webhook_rename(tokenkey, &token);
+                        self.audit(AuditEvent::Update, tokenkey, Some(&token));
+                        Some(UpdateResponsePayload { token, meta })
+                    } else {
+                        self.metrics
+                            .observe_lifetime(expires.saturating_duration_since(created));
+                        self.webhook_remove(tokenkey);
+                        self.audit(AuditEvent::Expire, tokenkey, None);
+                        None
+                    }
+                },
+            )
+            .ok_or(TokenUpdateFailed::InvalidToken)
+    }
+
+    /// Merge the metadata update into the existing metadata, without issuing a new token
+    /// or changing its expiry
+    #[allow(clippy::significant_drop_tightening)] // the lock also covers the index and tags
+    pub fn patch_metadata(
+        &self,
+        token: &Guid,
+        update: MetaData,
+    ) -> Result<MetaData, TokenUpdateFailed> {
+        let mut tokens = self.write_tokens();
+
+        let mut meta = tokens
+            .get(token)
+            .filter(|entry| self.is_valid(entry.expires, Instant::now()))
+            .map(|entry| entry.meta.clone())
+            .ok_or(TokenUpdateFailed::InvalidToken)?;
+        meta.extend(update);
+        self.check_unique(&tokens, &meta, Some(token))?;
+
+        let entry = tokens
+            .get_mut(token)
+            .ok_or(TokenUpdateFailed::InvalidToken)?;
+        self.index_remove(token, &entry.meta);
+        self.index_insert(token, &meta);
+        entry.meta.clone_from(&meta);
+        self.audit(AuditEvent::Patch, token, None);
+
+        Ok(meta)
+    }
+
+    /// Postpone the expiry of a valid token by `by`, counted from its current expiry
+    /// rather than from now, but at most the max lifetime from now; returns the new expiry
+    pub fn extend_token(
+        &self,
+        token: &Guid,
+        by: DurationHuman,
+    ) -> Result<DateTime<Utc>, TokenUpdateFailed> {
+        let now = Instant::now();
+        let latest = now.checked_add((&self.max_lifetime).into());
+
+        self.write_tokens()
+            .get_mut(token)
+            .filter(|entry| self.is_valid(entry.expires, now))
+            .map(|entry| {
+                let extended = entry.expires.checked_add((&by).into());
+                // the earliest of both, or as is when neither fits in an instant
+                entry.expires = [extended, latest]
+                    .into_iter()
+                    .flatten()
+                    .min()
+                    .unwrap_or(entry.expires);
+                self.audit(AuditEvent::Extend, token, None);
+
+                self.to_utc(entry.expires)
             })
+            .ok_or(TokenUpdateFailed::InvalidToken)
     }
 
-    pub fn remove_expired_tokens(&self) -> Result<PurgeResult, RwLockNotAcquired> {
-        self.tokens
-            .write()
-            .or(Err(RwLockNotAcquired))
-            .map(|mut tokens| {
-                let now = Instant::now();
+    /// Move a valid token to a new id, generated like for a new token when `new` is `None`,
+    /// keeping its metadata, expiry and tags; returns the new id
+    pub fn rekey(&self, old: &Guid, new: Option<Guid>) -> Result<Guid, TokenUpdateFailed> {
+        let mut tokens = self.write_tokens();
 
-                let tokens_before = tokens.len();
-                tokens.retain(|_key, (expires, _meta)| *expires >= now);
+        tokens
+            .get(old)
+            .filter(|entry| self.is_valid(entry.expires, Instant::now()))
+            .ok_or(TokenUpdateFailed::InvalidToken)?;
 
-                let tokens = tokens.len();
+        let new = new.unwrap_or_else(|| Uuid::new_v4().to_string());
+        if tokens.contains_key(&new) {
+            return Err(TokenUpdateFailed::TokenInUse);
+        }
+
+        tokens
+            .remove(old)
+            .map(|entry| {
+                self.index_remove(old, &entry.meta);
+                let tags = self.write_tags().remove(old);
+
+                self.index_insert(&new, &entry.meta);
+                self.write_tags().insert(&new, tags);
+                self.webhook_rename(old, &new);
+                tokens.insert(new.clone(), entry);
+                self.audit(AuditEvent::Rekey, old, Some(&new));
+
+                new
+            })
+            .ok_or(TokenUpdateFailed::InvalidToken)
+    }
 
-                PurgeResult {
-                    tokens,
-                    purged: tokens_before - tokens,
+    pub fn remove_expired_tokens(&self) -> PurgeResult {
+        let mut tokens = self.write_tokens();
+        let now = Instant::now();
+
+        let tokens_before = tokens.len();
+        tokens.retain(|key, entry| {
+            let keep = entry.expires >= now;
+            if !keep {
+                self.metrics
+                    .observe_lifetime(entry.expires.saturating_duration_since(entry.created));
+                self.index_remove(key, &entry.meta);
+                self.write_tags().remove(key);
+                if let Some(ref webhooks) = self.webhooks {
+                    webhooks.expired(key, &entry.meta);
                 }
+                self.audit(AuditEvent::Expire, key, None);
+            }
+
+            keep
+        });
+
+        let tokens_after = tokens.len();
+        drop(tokens);
+
+        PurgeResult {
+            tokens: tokens_after,
+            purged: tokens_before - tokens_after,
+        }
+    }
+
+    /// Whether each of the tokens is valid, and when it expires; in a single pass under the read lock
+    pub fn validate_many(&self, tokens_to_validate: &[Guid]) -> HashMap<Guid, TokenValidity> {
+        let tokens = self.read_tokens();
+        let now = Instant::now();
+
+        tokens_to_validate
+            .iter()
+            .map(|token| {
+                let expires = tokens.get(token).map(|entry| entry.expires);
+                let validity = TokenValidity {
+                    valid: expires.is_some_and(|expires| self.is_valid(expires, now)),
+                    expires: expires.map(|expires| self.to_utc(expires)),
+                };
+
+                (token.clone(), validity)
             })
+            .collect()
+    }
+
+    /// All valid tokens of which the metadata field configured as index key has this value;
+    /// expired tokens remain in the index until purged, so are left out here
+    pub fn find_by(&self, key_value: &str) -> Vec<Guid> {
+        let tokens = self.read_tokens();
+        let index = self.read_index();
+        let now = Instant::now();
+
+        index
+            .get(key_value)
+            .into_iter()
+            .flatten()
+            .filter(|token| {
+                tokens
+                    .get(*token)
+                    .is_some_and(|entry| self.is_valid(entry.expires, now))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Expiry timestamps are translated from the monotonic clock, counting from the wall clock
+    /// at startup; warn when both clocks drifted apart, as those timestamps are then off
+    pub fn check_clock_drift(&self) {
+        let monotonic = DurationHuman::from(self.started_at_instant.elapsed());
+        let wall_clock = DurationHuman::from(
+            (Utc::now() - self.started_at_utc)
+                .to_std()
+                .unwrap_or_default(),
+        );
+
+        if let Some(drift) = excessive_drift(&monotonic, &wall_clock, &self.max_clock_drift) {
+            warn!(
+                "Clock drift of {drift:#} since startup, dumped expiry timestamps are off by that much"
+            );
+        }
     }
 
     pub fn dump_meta(&self) {
-        if let Ok(tokens) = self.tokens.read() {
-            let report = tokens
-                .iter()
-                .map(|(_, (expires, meta))| {
-                    let duration = expires.duration_since(self.started_at_instant);
-
-                    // let's assume no wrap occurs, otherwise funny debug log
-                    #[allow(clippy::cast_possible_wrap)]
-                    DumpEntry::new(
-                        self.started_at_utc + chrono::Duration::seconds(duration.as_secs() as i64),
-                        meta,
+        match self.dump_report(None) {
+            Ok(report) => debug!("DUMP: {}", report),
+            Err(err) => error!("DUMP failed: {}", err),
+        }
+    }
+
+    /// All metadata and the associated timestamps, as JSON; only the given metadata `fields`
+    /// when projected
+    ///
+    /// ## Errors
+    /// when the report can not be serialized
+    #[allow(clippy::significant_drop_tightening)] // the report borrows the metadata
+    pub fn dump_report(&self, fields: Option<&HashSet<&str>>) -> Result<String, serde_json::Error> {
+        self.check_clock_drift();
+
+        let tokens = self.read_tokens();
+        let report = tokens
+            .values()
+            .map(|entry| {
+                let meta = fields.map_or(Cow::Borrowed(&entry.meta), |fields| {
+                    Cow::Owned(
+                        entry
+                            .meta
+                            .iter()
+                            .filter(|(key, _value)| fields.contains(key.as_str()))
+                            .map(|(key, value)| (key.clone(), value.clone()))
+                            .collect(),
                     )
-                })
-                .collect::<Vec<DumpEntry>>();
+                });
 
-            if let Ok(report) = serde_json::to_string(&report) {
-                debug!("DUMP: {}", report);
-            }
+                DumpEntry::new(self.to_utc(entry.created), self.to_utc(entry.expires), meta)
+            })
+            .collect::<Vec<DumpEntry>>();
+
+        if self.dump_pretty {
+            serde_json::to_string_pretty(&report)
+        } else {
+            serde_json::to_string(&report)
         }
     }
 
@@ -133,10 +599,225 @@ impl TokenStore {
         if let Some(ref handle) = self.handle {
             handle.shutdown();
         }
+        self.shutdown_notify.notify_one();
+    }
+
+    /// Completes once GET /shutdown asked to stop, for servers not driven by the handle
+    pub async fn shutdown_requested(&self) {
+        self.shutdown_notify.notified().await;
     }
 }
 
+// A panic while holding a lock poisons it, which would fail every request from then on.
+// Recovering the guard trades consistency for availability: the tokens may have been left
+// half updated by the panicking thread, but the server keeps serving. The poison is cleared
+// once recovered, so the warning is logged once per panic rather than on every request.
 impl TokenStore {
+    fn read_tokens(&self) -> RwLockReadGuard<'_, TokensByID> {
+        self.tokens.read().unwrap_or_else(|poisoned| {
+            warn!("Recovering the tokens from a lock poisoned by a panic");
+            self.tokens.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_tokens(&self) -> TokensWriteGuard<'_> {
+        let tokens = self.tokens.write().unwrap_or_else(|poisoned| {
+            warn!("Recovering the tokens from a lock poisoned by a panic");
+            self.tokens.clear_poison();
+            poisoned.into_inner()
+        });
+
+        TokensWriteGuard {
+            tokens,
+            _audit: WritePendingAudit(self.audit_log.as_ref()),
+        }
+    }
+
+    fn read_index(&self) -> RwLockReadGuard<'_, TokensByIndexValue> {
+        self.index.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_index(&self) -> RwLockWriteGuard<'_, TokensByIndexValue> {
+        self.index.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_tags(&self) -> RwLockWriteGuard<'_, TagIndex> {
+        self.tags.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl TokenStore {
+    /// Translate an instant, like the expiry or creation, into a wall clock timestamp,
+    /// keeping sub-second precision
+    fn to_utc(&self, instant: Instant) -> DateTime<Utc> {
+        let duration = instant.duration_since(self.started_at_instant);
+        // nano seconds in an i64 last for over 292 years after the server started
+        let nanos = i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX);
+
+        self.started_at_utc + chrono::Duration::nanoseconds(nanos)
+    }
+
+    /// The valid token of which the metadata `key` has this `value`, using the index when
+    /// it is on that key; while the caller holds the lock
+    fn find_valid_by(&self, tokens: &TokensByID, key: &str, value: &str) -> Option<Guid> {
+        let now = Instant::now();
+        let is_valid = |token: &Guid| {
+            tokens
+                .get(token)
+                .is_some_and(|entry| self.is_valid(entry.expires, now))
+        };
+
+        if self.index_key.as_deref() == Some(key) {
+            self.read_index()
+                .get(value)
+                .and_then(|index| index.iter().find(|token| is_valid(token)).cloned())
+        } else {
+            tokens
+                .iter()
+                .find(|(token, entry)| {
+                    is_valid(token) && metadata_value(&entry.meta, key).as_deref() == Some(value)
+                })
+                .map(|(token, _)| token.clone())
+        }
+    }
+
+    /// Refuse a new token once the soft cap is reached, while the caller holds the write lock
+    fn check_soft_cap(&self, tokens: &TokensByID) -> Result<(), CreateFailed> {
+        match self.soft_cap {
+            Some(soft_cap) if tokens.len() >= soft_cap.get() => Err(CreateFailed::SoftCapReached {
+                soft_cap: soft_cap.get(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether no other valid token than `updating` has the same value for the unique key,
+    /// while the caller holds the write lock
+    fn check_unique(
+        &self,
+        tokens: &TokensByID,
+        metadata: &MetaData,
+        updating: Option<&str>,
+    ) -> Result<(), NotUnique> {
+        let Some(key) = self.unique_key.as_deref() else {
+            return Ok(());
+        };
+
+        match metadata_value(metadata, key) {
+            Some(value)
+                if self
+                    .find_valid_by(tokens, key, &value)
+                    .is_some_and(|token| Some(token.as_str()) != updating) =>
+            {
+                Err(NotUnique {
+                    key: key.to_string(),
+                    value,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Insert a new token, while the caller holds the write lock; it expires after the
+    /// token lifetime, unless given an expiry
+    fn insert_token(
+        &self,
+        tokens: &mut TokensByID,
+        metadata: MetaData,
+        tags: Vec<String>,
+        webhook: Option<Uri>,
+        expires: Option<Instant>,
+    ) -> CreatedToken {
+        let (token, after_lifetime) = self.new_token();
+        let expires = expires.unwrap_or(after_lifetime);
+
+        tokens.insert(
+            token.clone(),
+            TokenEntry {
+                expires,
+                meta: metadata.clone(),
+                created: Instant::now(),
+            },
+        );
+        self.index_insert(&token, &metadata);
+        self.write_tags().insert(&token, tags);
+        self.webhook_insert(&token, webhook);
+        self.audit(AuditEvent::Create, &token, None);
+        self.track_high_water_mark(tokens.len());
+
+        CreatedToken {
+            token,
+            meta: metadata,
+            expires: self.to_utc(expires),
+        }
+    }
+
+    fn track_high_water_mark(&self, token_count: usize) {
+        if self.metrics.record_token_count(token_count) {
+            let uptime = DurationHuman::from(StdDuration::from_secs(
+                self.started_at_instant.elapsed().as_secs(),
+            ));
+            info!("New high-water mark of {token_count} tokens, after {uptime:#} uptime");
+        }
+    }
+
+    fn index_value(&self, meta: &MetaData) -> Option<String> {
+        self.index_key
+            .as_ref()
+            .and_then(|key| metadata_value(meta, key))
+    }
+
+    fn index_insert(&self, token: &str, meta: &MetaData) {
+        if let Some(value) = self.index_value(meta) {
+            let mut index = self.write_index();
+            index.entry(value).or_default().insert(token.to_string());
+        }
+    }
+
+    fn index_remove(&self, token: &str, meta: &MetaData) {
+        if let Some(value) = self.index_value(meta) {
+            let mut index = self.write_index();
+            if let Some(tokens) = index.get_mut(&value) {
+                tokens.remove(token);
+                if tokens.is_empty() {
+                    index.remove(&value);
+                }
+            }
+        }
+    }
+
+    fn webhook_insert(&self, token: &str, webhook: Option<Uri>) {
+        if let (Some(webhooks), Some(url)) = (&self.webhooks, webhook) {
+            webhooks.insert(token, url);
+        }
+    }
+
+    fn webhook_remove(&self, token: &str) {
+        if let Some(ref webhooks) = self.webhooks {
+            webhooks.remove(token);
+        }
+    }
+
+    fn webhook_rename(&self, old: &str, new: &str) {
+        if let Some(ref webhooks) = self.webhooks {
+            webhooks.rename(old, new);
+        }
+    }
+
+    #[inline]
+    fn audit(&self, event: AuditEvent, token: &str, renewed_as: Option<&str>) {
+        if let Some(ref audit_log) = self.audit_log {
+            audit_log.record(event, token, renewed_as);
+        }
+    }
+
+    /// Whether a token that expires at `expires` is still valid at `now`, including the grace period
+    #[inline]
+    fn is_valid(&self, expires: Instant, now: Instant) -> bool {
+        self.grace + expires > now
+    }
+
     #[inline]
     fn new_token(&self) -> (String, Instant) {
         (
@@ -151,10 +832,706 @@ impl Default for TokenStore {
         Self {
             tokens: RwLock::default(),
             token_lifetime: DurationHuman::default(),
+            min_lifetime: DurationHuman::new(0),
+            max_lifetime: DurationHuman::MAX,
+            grace: DurationHuman::new(0),
+            max_clock_drift: DurationHuman::ONE_SECOND,
+            soft_cap: None,
+            dump_pretty: false,
             // the two started_xxx dields are only required to show expiration timestamp in human readable format in dump
             started_at_instant: Instant::now(),
             started_at_utc: chrono::Utc::now(),
             handle: None,
+            shutdown_notify: Notify::new(),
+            audit_log: None,
+            webhooks: None,
+            index_key: None,
+            unique_key: None,
+            index: RwLock::default(),
+            tags: RwLock::default(),
+            metrics: Metrics::default(),
+        }
+    }
+}
+
+impl Deref for TokensWriteGuard<'_> {
+    type Target = TokensByID;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tokens
+    }
+}
+
+impl DerefMut for TokensWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tokens
+    }
+}
+
+impl Drop for WritePendingAudit<'_> {
+    fn drop(&mut self) {
+        if let Some(audit_log) = self.0 {
+            audit_log.write_pending();
+        }
+    }
+}
+
+/// How far both clocks drifted apart, when that is further than `max_clock_drift`
+fn excessive_drift(
+    monotonic: &DurationHuman,
+    wall_clock: &DurationHuman,
+    max_clock_drift: &DurationHuman,
+) -> Option<DurationHuman> {
+    let drift = monotonic.abs_diff(wall_clock);
+
+    drift.is_longer_than(max_clock_drift).then_some(drift)
+}
+
+/// The value of a metadata field; strings as is, other values as JSON
+fn metadata_value(meta: &MetaData, key: &str) -> Option<String> {
+    meta.get(key).map(|value| {
+        value
+            .as_str()
+            .map_or_else(|| value.to_string(), ToString::to_string)
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_in_result, clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use std::{error::Error, num::NonZeroUsize, thread, time::Duration};
+
+    use chrono::{NaiveDateTime, Utc};
+    use duration_human::DurationHuman;
+    use serde_json::{json, Value as JsonValue};
+    use uuid::Uuid;
+
+    use super::{
+        excessive_drift, AuditLog, CreateFailed, MetaData, TokenStore, TokenUpdateFailed,
+        UpsertResult,
+    };
+
+    type TestResult = Result<(), Box<dyn Error>>;
+
+    fn store() -> Result<TokenStore, Box<dyn Error>> {
+        Ok(TokenStore::default().with_token_lifetime(DurationHuman::parse("1h")?))
+    }
+
+    fn meta(value: JsonValue) -> MetaData {
+        match value {
+            JsonValue::Object(meta) => meta,
+            _ => MetaData::default(),
+        }
+    }
+
+    /// A timestamp of the single entry in the dump
+    fn dumped(store: &TokenStore, field: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+        let report: JsonValue = serde_json::from_str(&store.dump_report(None)?)?;
+        let timestamp = report[0][field].as_str().ok_or("no such timestamp")?;
+
+        Ok(NaiveDateTime::parse_from_str(
+            timestamp,
+            "%Y-%m-%d %H:%M:%S%.3f",
+        )?)
+    }
+
+    #[test]
+    fn recovers_from_a_poisoned_lock() -> TestResult {
+        let store = store()?;
+        let created =
+            store.create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?;
+
+        let panicked = thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _tokens = store.write_tokens();
+                    panic!("poison the lock on the tokens");
+                })
+                .join()
+        });
+        assert!(panicked.is_err());
+        assert!(store.tokens.is_poisoned());
+
+        let validity = store.validate_many(std::slice::from_ref(&created.token));
+        assert!(validity[&created.token].valid);
+        assert!(!store.tokens.is_poisoned());
+
+        store.create_token_full(meta(json!({ "user": "bob" })), Vec::new(), None)?;
+        store.remove_token(&created.token);
+        assert_eq!(store.remove_expired_tokens().tokens, 1);
+        assert_eq!(store.clear(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn audit_log_appends_a_json_line_per_event() -> TestResult {
+        let path = std::env::temp_dir().join(format!("token_server-audit-{}.log", Uuid::new_v4()));
+        let store = store()?.with_audit_log(Some(AuditLog::open(&path)?));
+
+        let token = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+        // on disk right away, without a purge or shutdown to flush it
+        assert_eq!(std::fs::read_to_string(&path)?.lines().count(), 1);
+        let renewed = store.update_token(&token, None)?.token;
+        store.patch_metadata(&renewed, meta(json!({ "year": 2022 })))?;
+        store.remove_token(&renewed);
+        store.create_token_expiring_at(MetaData::new(), Vec::new(), None, Utc::now())?;
+        thread::sleep(Duration::from_millis(10));
+        store.remove_expired_tokens();
+        // renewing an expired token removes it as expired, like the purge does
+        let expired = store
+            .create_token_expiring_at(MetaData::new(), Vec::new(), None, Utc::now())?
+            .token;
+        thread::sleep(Duration::from_millis(10));
+        assert!(store.update_token(&expired, None).is_err());
+
+        let log = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        let entries = log
+            .lines()
+            .map(serde_json::from_str::<JsonValue>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let events = entries
+            .iter()
+            .map(|entry| entry["event"].as_str().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            ["create", "update", "patch", "remove", "create", "expire", "create", "expire"]
+        );
+        assert!(entries[1]["renewed_as"].is_string());
+        assert!(entries.iter().all(|entry| entry["at"].is_string()));
+        assert!(!log.contains(&token) && !log.contains(&renewed));
+        Ok(())
+    }
+
+    #[test]
+    fn high_water_mark_rises_only_past_the_previous_max() -> TestResult {
+        let store = store()?;
+        let tokens = (0..3)
+            .map(|_| {
+                store
+                    .create_token_full(MetaData::new(), Vec::new(), None)
+                    .map(|created| created.token)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(store.metrics().tokens_high_water_mark(), 3);
+
+        store.remove_token(&tokens[0]);
+        store.remove_token(&tokens[1]);
+        store.create_token_full(MetaData::new(), Vec::new(), None)?;
+        assert_eq!(store.metrics().tokens_high_water_mark(), 3);
+
+        store.create_token_full(MetaData::new(), Vec::new(), None)?;
+        store.upsert_by("user", "alice", MetaData::new())?;
+        assert_eq!(store.metrics().tokens_high_water_mark(), 4);
+        assert!(!store.metrics().record_token_count(4));
+        assert!(store.metrics().record_token_count(5));
+        Ok(())
+    }
+
+    #[test]
+    fn expired_lifetimes_show_in_the_metrics() -> TestResult {
+        let store = TokenStore::default().with_token_lifetime(DurationHuman::parse("50ms")?);
+        for _ in 0..2 {
+            store.create_token_full(MetaData::new(), Vec::new(), None)?;
         }
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(store.remove_expired_tokens().purged, 2);
+
+        let metrics = store.metrics().to_string();
+        assert!(
+            metrics.contains("\ntoken_server_token_lifetime_seconds_count 2\n"),
+            "{metrics}"
+        );
+        // each lived its lifetime, give or take the millisecond it is truncated to
+        let sum = metrics
+            .lines()
+            .find_map(|line| line.strip_prefix("token_server_token_lifetime_seconds_sum "))
+            .ok_or("no lifetime sum")?
+            .parse::<f64>()?;
+        assert!((0.098..=0.1).contains(&sum), "{metrics}");
+        Ok(())
+    }
+
+    #[test]
+    fn clock_drift_beyond_the_max_is_excessive() -> TestResult {
+        let max_clock_drift = DurationHuman::parse("1s")?;
+        let monotonic = DurationHuman::parse("10s")?;
+
+        for (wall_clock, drift) in [
+            ("10s", None),
+            ("10s 500ms", None),
+            ("11s", None),
+            ("11s 1ms", Some("1s 1ms")),
+            ("12s", Some("2s")),
+            ("8s", Some("2s")),
+        ] {
+            let wall_clock = DurationHuman::parse(wall_clock)?;
+            let drift = drift.map(DurationHuman::parse).transpose()?;
+            assert_eq!(
+                excessive_drift(&monotonic, &wall_clock, &max_clock_drift),
+                drift,
+                "{wall_clock}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn remove_by_tag_leaves_other_tokens_alone() -> TestResult {
+        let store = store()?;
+        let tags = |tags: &[&str]| tags.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let beta = store.create_token_full(MetaData::new(), tags(&["tenant-7", "beta"]), None)?;
+        let renewed_beta = store.update_token(&beta.token, None)?.token;
+        let tenant = store.create_token_full(MetaData::new(), tags(&["tenant-7"]), None)?;
+        let untagged = store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+
+        assert_eq!(store.remove_by_tag("beta"), 1);
+        assert_eq!(store.remove_by_tag("beta"), 0);
+        assert_eq!(store.remove_by_tag("unknown"), 0);
+
+        let validity =
+            store.validate_many(&[renewed_beta.clone(), tenant.token.clone(), untagged.clone()]);
+        assert!(!validity[&renewed_beta].valid);
+        assert!(validity[&tenant.token].valid);
+        assert!(validity[&untagged].valid);
+
+        assert_eq!(store.remove_by_tag("tenant-7"), 1);
+        assert!(store.validate_many(std::slice::from_ref(&untagged))[&untagged].valid);
+        Ok(())
+    }
+
+    #[test]
+    fn create_token_full_returns_the_whole_record() -> TestResult {
+        let store = store()?;
+        let before = Utc::now();
+        let created =
+            store.create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?;
+        let after = Utc::now();
+
+        assert!(store.validate_many(std::slice::from_ref(&created.token))[&created.token].valid);
+        assert_eq!(created.meta, meta(json!({ "user": "alice" })));
+        let lifetime = chrono::Duration::hours(1);
+        // the wall clock at startup plus the monotonic time since, so allow a little slack
+        let slack = chrono::Duration::milliseconds(100);
+        assert!(created.expires >= before + lifetime - slack);
+        assert!(created.expires <= after + lifetime + slack);
+        Ok(())
+    }
+
+    #[test]
+    fn an_expiry_below_the_min_lifetime_is_postponed() -> TestResult {
+        let store = store()?.with_min_lifetime(DurationHuman::parse("10min")?);
+        let now = Utc::now();
+        let slack = chrono::Duration::milliseconds(100);
+
+        let clamped = store.create_token_expiring_at(
+            MetaData::new(),
+            Vec::new(),
+            None,
+            now + chrono::Duration::minutes(1),
+        )?;
+        let floor = now + chrono::Duration::minutes(10);
+        assert!(clamped.expires >= floor - slack && clamped.expires <= floor + slack);
+
+        let requested = now + chrono::Duration::minutes(30);
+        let kept = store.create_token_expiring_at(MetaData::new(), Vec::new(), None, requested)?;
+        assert!(kept.expires >= requested - slack && kept.expires <= requested + slack);
+        Ok(())
+    }
+
+    #[test]
+    fn patch_keeps_the_token_and_its_expiry() -> TestResult {
+        let store = store()?;
+        let created = store.create_token_full(
+            meta(json!({ "user": "alice", "year": 2022 })),
+            Vec::new(),
+            None,
+        )?;
+
+        let patched =
+            store.patch_metadata(&created.token, meta(json!({ "year": 2023, "period": 11 })))?;
+        assert_eq!(
+            patched,
+            meta(json!({ "user": "alice", "year": 2023, "period": 11 }))
+        );
+
+        let validity = &store.validate_many(std::slice::from_ref(&created.token))[&created.token];
+        assert!(validity.valid);
+        assert_eq!(validity.expires, Some(created.expires));
+
+        let renewed = store.update_token(&created.token, None)?;
+        assert_eq!(renewed.meta, patched);
+        Ok(())
+    }
+
+    #[test]
+    fn extend_is_bounded_by_the_max_lifetime() -> TestResult {
+        let store = store()?.with_max_lifetime(DurationHuman::parse("2h")?);
+        let token = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+
+        let expires = store.extend_token(&token, DurationHuman::parse("30min")?)?;
+        assert!(expires > Utc::now() + chrono::Duration::minutes(89));
+        assert!(expires <= Utc::now() + chrono::Duration::hours(2));
+
+        let expires = store.extend_token(&token, DurationHuman::parse("500 years")?)?;
+        assert!(expires > Utc::now() + chrono::Duration::minutes(119));
+        assert!(expires <= Utc::now() + chrono::Duration::hours(2));
+        Ok(())
+    }
+
+    #[test]
+    fn rekey_moves_the_token() -> TestResult {
+        let store = store()?;
+        let old = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+
+        let new = store.rekey(&old, None)?;
+        assert_ne!(new, old);
+
+        let validity = store.validate_many(&[old.clone(), new.clone()]);
+        assert!(!validity[&old].valid);
+        assert!(validity[&new].valid);
+        assert!(matches!(
+            store.rekey(&old, None),
+            Err(TokenUpdateFailed::InvalidToken)
+        ));
+        assert_eq!(
+            store.patch_metadata(&new, MetaData::new())?,
+            meta(json!({ "user": "alice" }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rekey_to_a_given_id_must_not_take_one_in_use() -> TestResult {
+        let store = store()?;
+        let old = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+        let other = store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+
+        assert!(matches!(
+            store.rekey(&old, Some(other)),
+            Err(TokenUpdateFailed::TokenInUse)
+        ));
+        assert!(store.validate_many(std::slice::from_ref(&old))[&old].valid);
+
+        let given = String::from("rotated-by-the-caller");
+        assert_eq!(store.rekey(&old, Some(given.clone()))?, given);
+        assert_eq!(
+            store.patch_metadata(&given, MetaData::new())?,
+            meta(json!({ "user": "alice" }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn index_follows_create_update_patch_rekey_and_remove() -> TestResult {
+        let store = store()?.with_index_key(Some(String::from("user")));
+        let token = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+        assert_eq!(store.find_by("alice"), std::slice::from_ref(&token));
+
+        let renewed = store.update_token(&token, None)?.token;
+        assert_eq!(store.find_by("alice"), std::slice::from_ref(&renewed));
+
+        store.patch_metadata(&renewed, meta(json!({ "user": "bob" })))?;
+        assert!(store.find_by("alice").is_empty());
+        assert_eq!(store.find_by("bob"), std::slice::from_ref(&renewed));
+
+        let rekeyed = store.rekey(&renewed, None)?;
+        assert_eq!(store.find_by("bob"), std::slice::from_ref(&rekeyed));
+
+        store.remove_token(&rekeyed);
+        assert!(store.find_by("bob").is_empty());
+        assert!(store.read_index().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_many_tells_valid_expired_and_unknown_apart() -> TestResult {
+        let store = store()?;
+        let valid = store
+            .create_token_full(MetaData::new(), Vec::new(), None)?
+            .token;
+        let expired = store
+            .create_token_expiring_at(MetaData::new(), Vec::new(), None, Utc::now())?
+            .token;
+        let unknown = Uuid::new_v4().to_string();
+        thread::sleep(Duration::from_millis(10));
+
+        let results = store.validate_many(&[valid.clone(), expired.clone(), unknown.clone()]);
+        assert_eq!(results.len(), 3);
+        assert!(results[&valid].valid && results[&valid].expires.is_some());
+        assert!(!results[&expired].valid && results[&expired].expires.is_some());
+        assert!(!results[&unknown].valid && results[&unknown].expires.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn grace_keeps_a_just_expired_token_valid_until_purged() -> TestResult {
+        let expired_now = |store: &TokenStore| {
+            store
+                .create_token_expiring_at(MetaData::new(), Vec::new(), None, Utc::now())
+                .map(|created| created.token)
+        };
+
+        let long_grace = store()?.with_grace(DurationHuman::parse("1h")?);
+        let within_grace = expired_now(&long_grace)?;
+        let renewed_within_grace = expired_now(&long_grace)?;
+        thread::sleep(Duration::from_millis(10));
+        assert!(long_grace.validate_many(std::slice::from_ref(&within_grace))[&within_grace].valid);
+        long_grace.update_token(&renewed_within_grace, None)?;
+
+        // the purge still removes it at its expiry
+        assert_eq!(long_grace.remove_expired_tokens().purged, 1);
+        assert!(
+            !long_grace.validate_many(std::slice::from_ref(&within_grace))[&within_grace].valid
+        );
+
+        let short_grace = store()?.with_grace(DurationHuman::parse("1ms")?);
+        let beyond_grace = expired_now(&short_grace)?;
+        thread::sleep(Duration::from_millis(10));
+        assert!(
+            !short_grace.validate_many(std::slice::from_ref(&beyond_grace))[&beyond_grace].valid
+        );
+        assert!(matches!(
+            short_grace.update_token(&beyond_grace, None),
+            Err(TokenUpdateFailed::InvalidToken)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn find_by_leaves_out_expired_tokens() -> TestResult {
+        let store = store()?.with_index_key(Some(String::from("user")));
+        let valid = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+        store.create_token_expiring_at(
+            meta(json!({ "user": "alice" })),
+            Vec::new(),
+            None,
+            Utc::now() - chrono::Duration::seconds(1),
+        )?;
+
+        assert_eq!(store.find_by("alice"), [valid]);
+        assert_eq!(store.read_index()["alice"].len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn upsert_creates_then_updates() -> TestResult {
+        let store = store()?;
+
+        let UpsertResult::Created(created) =
+            store.upsert_by("user", "alice", meta(json!({ "year": 2022 })))?
+        else {
+            return Err("the first upsert did not create".into());
+        };
+        assert_eq!(created.meta, meta(json!({ "user": "alice", "year": 2022 })));
+
+        let UpsertResult::Updated(updated) =
+            store.upsert_by("user", "alice", meta(json!({ "period": 11 })))?
+        else {
+            return Err("the second upsert did not update".into());
+        };
+        assert_eq!(updated.token, created.token);
+        assert_eq!(
+            updated.meta,
+            meta(json!({ "user": "alice", "year": 2022, "period": 11 }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_upserts_do_not_create_duplicates() -> TestResult {
+        for index_key in [None, Some("user".to_string())] {
+            let store = store()?.with_index_key(index_key);
+
+            let results = thread::scope(|scope| {
+                let upserts = (0..8)
+                    .map(|_| scope.spawn(|| store.upsert_by("user", "alice", MetaData::new())))
+                    .collect::<Vec<_>>();
+                upserts
+                    .into_iter()
+                    .map(|upsert| upsert.join().map_err(|_| "an upsert panicked"))
+                    .collect::<Result<Vec<_>, _>>()
+            })?;
+
+            let created = results
+                .into_iter()
+                .filter(|result| matches!(result, Ok(UpsertResult::Created(_))))
+                .count();
+            assert_eq!(created, 1);
+            assert_eq!(store.clear(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unique_key_refuses_a_second_valid_token() -> TestResult {
+        let store = store()?.with_unique_key(Some("user".to_string()));
+        let alice = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+        let bob = store
+            .create_token_full(meta(json!({ "user": "bob" })), Vec::new(), None)?
+            .token;
+
+        assert!(matches!(
+            store.create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None),
+            Err(CreateFailed::NotUnique(_))
+        ));
+        assert!(matches!(
+            store.patch_metadata(&bob, meta(json!({ "user": "alice" }))),
+            Err(TokenUpdateFailed::NotUnique(_))
+        ));
+        assert!(matches!(
+            store.update_token(&bob, Some(meta(json!({ "user": "alice" })))),
+            Err(TokenUpdateFailed::NotUnique(_))
+        ));
+        assert!(store.validate_many(std::slice::from_ref(&bob))[&bob].valid);
+
+        assert!(matches!(
+            store.upsert_by("user", "bob", meta(json!({ "user": "alice" }))),
+            Err(CreateFailed::NotUnique(_))
+        ));
+        assert!(matches!(
+            store.upsert_by("role", "admin", meta(json!({ "user": "alice" }))),
+            Err(CreateFailed::NotUnique(_))
+        ));
+
+        store.patch_metadata(&alice, meta(json!({ "user": "alice", "role": "admin" })))?;
+        let renewed = store.update_token(&alice, Some(meta(json!({ "user": "alice" }))))?;
+        assert!(matches!(
+            store.upsert_by("user", "alice", meta(json!({ "year": 2022 })))?,
+            UpsertResult::Updated(updated) if updated.token == renewed.token
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn soft_cap_refuses_new_tokens() -> TestResult {
+        let store = store()?.with_soft_cap(NonZeroUsize::new(2));
+        let alice = store
+            .create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?
+            .token;
+        store.upsert_by("user", "bob", MetaData::new())?;
+
+        let soft_cap_reached =
+            |result| matches!(result, Err(CreateFailed::SoftCapReached { soft_cap: 2 }));
+        assert!(soft_cap_reached(
+            store
+                .create_token_full(meta(json!({ "user": "carol" })), Vec::new(), None)
+                .map(|created| created.token)
+        ));
+        assert!(soft_cap_reached(
+            store
+                .upsert_by("user", "carol", MetaData::new())
+                .map(|_| String::new())
+        ));
+        assert!(soft_cap_reached(
+            store
+                .create_token_expiring_at(
+                    MetaData::new(),
+                    Vec::new(),
+                    None,
+                    Utc::now() + chrono::Duration::minutes(5),
+                )
+                .map(|created| created.token)
+        ));
+
+        // the existing tokens remain usable
+        store.upsert_by("user", "bob", meta(json!({ "year": 2022 })))?;
+        let renewed = store.update_token(&alice, None)?;
+        assert!(store.validate_many(std::slice::from_ref(&renewed.token))[&renewed.token].valid);
+        Ok(())
+    }
+
+    #[test]
+    fn soft_cap_holds_for_concurrent_creates() -> TestResult {
+        let store = store()?.with_soft_cap(NonZeroUsize::new(10));
+
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..5 {
+                        let _created = store.create_token_full(MetaData::new(), Vec::new(), None);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(store.clear(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn dump_report_keeps_sub_second_expiry() -> TestResult {
+        let store = TokenStore::default().with_token_lifetime(DurationHuman::parse("1500ms")?);
+        store.create_token_full(MetaData::new(), Vec::new(), None)?;
+
+        let lifetime = dumped(&store, "expires")? - dumped(&store, "created")?;
+        assert!(
+            (1499..=1501).contains(&lifetime.num_milliseconds()),
+            "{lifetime}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dump_report_is_pretty_only_when_asked() -> TestResult {
+        for dump_pretty in [false, true] {
+            let store = store()?.with_dump_pretty(dump_pretty);
+            store.create_token_full(meta(json!({ "user": "alice" })), Vec::new(), None)?;
+
+            let report = store.dump_report(None)?;
+            assert_eq!(report.contains('\n'), dump_pretty, "{report}");
+            assert_eq!(report.contains("  \""), dump_pretty, "{report}");
+            assert!(serde_json::from_str::<JsonValue>(&report)?.is_array());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dump_report_projects_fields() -> TestResult {
+        let store = store()?;
+        store.create_token_full(
+            meta(json!({ "user": "alice", "secret": "hunter2" })),
+            Vec::new(),
+            None,
+        )?;
+
+        let fields = std::iter::once("user").collect();
+        let report: JsonValue = serde_json::from_str(&store.dump_report(Some(&fields))?)?;
+        let entries = report.as_array().ok_or("the report is not an array")?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["meta"], json!({ "user": "alice" }));
+        Ok(())
+    }
+
+    #[test]
+    fn dump_report_without_fields_has_all_metadata() -> TestResult {
+        let store = store()?;
+        let metadata = json!({ "user": "alice", "secret": "hunter2", "roles": ["admin"] });
+        store.create_token_full(meta(metadata.clone()), Vec::new(), None)?;
+
+        let report: JsonValue = serde_json::from_str(&store.dump_report(None)?)?;
+        let entries = report.as_array().ok_or("the report is not an array")?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["meta"], metadata);
+        Ok(())
     }
 }
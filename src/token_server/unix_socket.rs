@@ -0,0 +1,184 @@
+use std::{future::Future, io, path::Path, time::Duration};
+
+use axum::Router;
+
+/// Serve on a Unix domain socket instead of TCP, until `shutdown` completes;
+/// the socket file is removed afterwards
+///
+/// A socket file left behind by a server that is gone, as nothing accepts a connection
+/// on it, is replaced
+///
+/// ## Errors
+/// when the socket can not be bound, e.g. because another server listens on it
+#[cfg(unix)]
+pub async fn serve_unix(
+    path: &Path,
+    app: Router,
+    header_timeout: Option<Duration>,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    use std::{
+        os::unix::fs::FileTypeExt,
+        pin::Pin,
+        task::{ready, Context, Poll},
+    };
+
+    use hyper::server::accept::Accept;
+    use tokio::net::{UnixListener, UnixStream};
+    use tracing::warn;
+
+    struct UnixAccept(UnixListener);
+
+    impl Accept for UnixAccept {
+        type Conn = UnixStream;
+        type Error = io::Error;
+
+        fn poll_accept(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+            let (stream, _peer) = ready!(self.0.poll_accept(cx))?;
+            Poll::Ready(Some(Ok(stream)))
+        }
+    }
+
+    let listener = match UnixListener::bind(path) {
+        Err(err)
+            if err.kind() == io::ErrorKind::AddrInUse
+                && std::fs::metadata(path)
+                    .is_ok_and(|metadata| metadata.file_type().is_socket())
+                && UnixStream::connect(path).await.is_err() =>
+        {
+            warn!("Replacing the stale socket file {}", path.display());
+            std::fs::remove_file(path)?;
+            UnixListener::bind(path)?
+        }
+        bound => bound?,
+    };
+    let mut server = hyper::Server::builder(UnixAccept(listener));
+    if let Some(timeout) = header_timeout {
+        server = server.http1_header_read_timeout(timeout);
+    }
+
+    let outcome = server
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await;
+
+    if let Err(err) = std::fs::remove_file(path) {
+        warn!(
+            "Could not remove the socket file {}: {}",
+            path.display(),
+            err
+        );
+    }
+
+    outcome.map_err(io::Error::other)
+}
+
+/// Unix domain sockets are not available on this platform
+///
+/// ## Errors
+/// always `io::ErrorKind::Unsupported`
+#[cfg(not(unix))]
+#[allow(clippy::unused_async)]
+pub async fn serve_unix(
+    _path: &Path,
+    _app: Router,
+    _header_timeout: Option<Duration>,
+    _shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--uds requires a Unix platform",
+    ))
+}
+
+#[cfg(all(test, unix))]
+#[allow(clippy::unwrap_in_result, clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use std::{
+        error::Error,
+        io,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use axum::{routing::post, Router};
+    use http::{header::CONTENT_TYPE, Method, Request, StatusCode};
+    use hyper::{client::conn, Body};
+    use serde_json::{json, Value as JsonValue};
+    use tokio::{net::UnixStream, sync::oneshot, task::JoinHandle};
+    use uuid::Uuid;
+
+    use super::{
+        super::{routes, TokenStore},
+        serve_unix,
+    };
+
+    type TestResult = Result<(), Box<dyn Error>>;
+
+    fn socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!("token_server-{}.sock", Uuid::new_v4()))
+    }
+
+    fn serve(path: PathBuf) -> (oneshot::Sender<()>, JoinHandle<io::Result<()>>) {
+        let app = Router::new()
+            .route("/token", post(routes::create_token))
+            .with_state(Arc::new(TokenStore::default()));
+        let (shutdown, shutdown_requested) = oneshot::channel::<()>();
+        let served = tokio::spawn(async move {
+            serve_unix(&path, app, None, async {
+                let _shutdown = shutdown_requested.await;
+            })
+            .await
+        });
+
+        (shutdown, served)
+    }
+
+    async fn connect(path: &Path) -> io::Result<UnixStream> {
+        for _ in 0..100 {
+            if let Ok(stream) = UnixStream::connect(path).await {
+                return Ok(stream);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        UnixStream::connect(path).await
+    }
+
+    #[tokio::test]
+    async fn creates_a_token_over_the_socket() -> TestResult {
+        let path = socket_path();
+        // left behind by a server that is gone
+        drop(std::os::unix::net::UnixListener::bind(&path)?);
+
+        let (shutdown, served) = serve(path.clone());
+        let (mut sender, connection) = conn::handshake(connect(&path).await?).await?;
+        tokio::spawn(connection);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/token")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                json!({ "meta": { "user": "alice" } }).to_string(),
+            ))?;
+        let response = sender.send_request(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: JsonValue =
+            serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await?)?;
+        assert!(body["token"].is_string());
+
+        // another server does not take over a socket that is in use
+        assert!(serve(path.clone()).1.await?.is_err());
+
+        drop(sender);
+        shutdown.send(()).map_err(|()| "the server stopped early")?;
+        served.await??;
+        assert!(!path.exists());
+        Ok(())
+    }
+}
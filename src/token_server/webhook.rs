@@ -0,0 +1,478 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::{Arc, PoisonError, RwLock, RwLockWriteGuard},
+    task::{Context, Poll},
+    time::Duration as StdDuration,
+    vec,
+};
+
+use duration_human::DurationHuman;
+use http::{header::CONTENT_TYPE, Request, Uri};
+use hyper::{
+    client::{connect::dns::Name, HttpConnector},
+    Body, Client,
+};
+use serde::Serialize;
+use tokio::{
+    net::lookup_host,
+    sync::mpsc::{self, error::TrySendError},
+    time::{sleep, timeout},
+};
+use tower::Service;
+use tracing::{debug, warn};
+
+use super::{
+    api::{Guid, MetaData},
+    InvalidWebhook,
+};
+
+const ATTEMPTS: usize = 3;
+const ATTEMPT_TIMEOUT: StdDuration = StdDuration::from_secs(5);
+
+/// The webhook of each token that has one, and the queue towards the dispatcher
+/// that POSTs to them once the purge removed the expired token
+pub struct Webhooks {
+    by_token: RwLock<HashMap<Guid, Uri>>,
+    queue: mpsc::Sender<ExpiredToken>,
+    allowed_hosts: AllowedHosts,
+}
+
+/// Spawn `run` to POST the queued expiries, one at a time
+pub struct WebhookDispatcher {
+    queue: mpsc::Receiver<ExpiredToken>,
+    client: Client<HttpConnector<PublicResolver>>,
+}
+
+/// The hosts a webhook may target, even though they are not public, e.g. a receiver
+/// on the local network
+#[derive(Clone)]
+struct AllowedHosts(Arc<Vec<String>>);
+
+/// Resolves a host name to its public addresses only, so a webhook can not reach the
+/// loopback, private or link-local network through a name that resolves there
+#[derive(Clone)]
+struct PublicResolver {
+    allowed_hosts: AllowedHosts,
+}
+
+struct ExpiredToken {
+    url: Uri,
+    body: ExpiredBody,
+}
+
+#[derive(Serialize)]
+struct ExpiredBody {
+    token: Guid,
+    meta: MetaData,
+}
+
+impl Webhooks {
+    /// At most `capacity` expiries wait for the dispatcher; more are dropped with a warning
+    ///
+    /// Only public addresses are posted to, apart from the `allowed_hosts`
+    pub fn new(capacity: NonZeroUsize, allowed_hosts: Vec<String>) -> (Self, WebhookDispatcher) {
+        let (sender, receiver) = mpsc::channel(capacity.get());
+        let allowed_hosts = AllowedHosts(Arc::new(allowed_hosts));
+        let resolver = PublicResolver {
+            allowed_hosts: allowed_hosts.clone(),
+        };
+
+        (
+            Self {
+                by_token: RwLock::default(),
+                queue: sender,
+                allowed_hosts,
+            },
+            WebhookDispatcher {
+                queue: receiver,
+                client: Client::builder().build(HttpConnector::new_with_resolver(resolver)),
+            },
+        )
+    }
+
+    /// Check the url to POST to, only plain http as the client has no TLS
+    ///
+    /// ## Errors
+    /// when it is not an absolute http url, or targets the loopback, private or link-local
+    /// network without being an allowed host
+    pub fn parse_url(&self, url: &str) -> Result<Uri, InvalidWebhook> {
+        let uri = url.parse::<Uri>().map_err(|_| InvalidWebhook::InvalidUrl {
+            url: url.to_string(),
+        })?;
+
+        let Some(host) = uri.host().filter(|_| uri.scheme_str() == Some("http")) else {
+            return Err(InvalidWebhook::UnsupportedScheme {
+                url: url.to_string(),
+            });
+        };
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let is_local = host.parse::<IpAddr>().map_or_else(
+            |_| {
+                let host = host.to_ascii_lowercase();
+                host == "localhost" || host.ends_with(".localhost")
+            },
+            |ip| !is_public(ip),
+        );
+
+        if is_local && !self.allowed_hosts.contains(host) {
+            Err(InvalidWebhook::NotPublic {
+                url: url.to_string(),
+            })
+        } else {
+            Ok(uri)
+        }
+    }
+
+    pub fn insert(&self, token: &str, url: Uri) {
+        self.write().insert(token.to_string(), url);
+    }
+
+    /// Forget the token, returning its webhook
+    pub fn remove(&self, token: &str) -> Option<Uri> {
+        self.write().remove(token)
+    }
+
+    /// Keep the webhook when the token is renewed or rekeyed
+    pub fn rename(&self, old: &str, new: &str) {
+        let mut by_token = self.write();
+        if let Some(url) = by_token.remove(old) {
+            by_token.insert(new.to_string(), url);
+        }
+    }
+
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    /// Queue the POST for an expired token that has a webhook, without waiting for it
+    pub fn expired(&self, token: &str, meta: &MetaData) {
+        let Some(url) = self.remove(token) else {
+            return;
+        };
+
+        let expired = ExpiredToken {
+            url,
+            body: ExpiredBody {
+                token: token.to_string(),
+                meta: meta.clone(),
+            },
+        };
+
+        match self.queue.try_send(expired) {
+            Ok(()) => {}
+            Err(TrySendError::Full(expired)) => {
+                warn!("WEBHOOK queue is full, dropped {}", expired.url);
+            }
+            Err(TrySendError::Closed(expired)) => {
+                warn!("WEBHOOK dispatcher stopped, dropped {}", expired.url);
+            }
+        }
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<Guid, Uri>> {
+        self.by_token
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl AllowedHosts {
+    fn contains(&self, host: &str) -> bool {
+        self.0
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+}
+
+impl Service<Name> for PublicResolver {
+    type Response = vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let allowed = self.allowed_hosts.contains(name.as_str());
+
+        Box::pin(async move {
+            let addrs = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || is_public(addr.ip()))
+                .collect::<Vec<_>>();
+
+            if addrs.is_empty() {
+                Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{name} does not resolve to a public address"),
+                ))
+            } else {
+                Ok(addrs.into_iter())
+            }
+        })
+    }
+}
+
+/// Neither loopback, private, link-local, shared, unspecified, broadcast nor multicast
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            let shared = first == 100 && (second & 0xc0) == 64;
+
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || shared
+                || first == 0
+                || ip.is_broadcast()
+                || ip.is_multicast())
+        }
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or_else(
+            || {
+                let first = ip.segments()[0];
+                let unique_local = (first & 0xfe00) == 0xfc00;
+                let link_local = (first & 0xffc0) == 0xfe80;
+
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || unique_local
+                    || link_local
+                    || ip.is_multicast())
+            },
+            |ip| is_public(IpAddr::V4(ip)),
+        ),
+    }
+}
+
+impl WebhookDispatcher {
+    pub async fn run(mut self) {
+        while let Some(expired) = self.queue.recv().await {
+            self.post(&expired).await;
+        }
+    }
+
+    /// Best-effort: a few attempts with a timeout each, backing off in between
+    async fn post(&self, expired: &ExpiredToken) {
+        let Ok(body) = serde_json::to_vec(&expired.body) else {
+            return;
+        };
+
+        let backoff = DurationHuman::exponential(
+            DurationHuman::ONE_SECOND,
+            2,
+            DurationHuman::from(StdDuration::from_secs(10)),
+        );
+        for (attempt, pause) in (1..=ATTEMPTS).zip(backoff) {
+            let request = match Request::post(expired.url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body.clone()))
+            {
+                Ok(request) => request,
+                Err(err) => {
+                    warn!("WEBHOOK {} failed: {}", expired.url, err);
+                    return;
+                }
+            };
+
+            match timeout(ATTEMPT_TIMEOUT, self.client.request(request)).await {
+                Ok(Ok(response)) if response.status().is_success() => {
+                    debug!("WEBHOOK {} -> {}", expired.url, response.status());
+                    return;
+                }
+                Ok(Ok(response)) => warn!(
+                    "WEBHOOK {} attempt {attempt} -> {}",
+                    expired.url,
+                    response.status()
+                ),
+                Ok(Err(err)) => warn!("WEBHOOK {} attempt {attempt} failed: {}", expired.url, err),
+                Err(_) => warn!("WEBHOOK {} attempt {attempt} timed out", expired.url),
+            }
+
+            if attempt < ATTEMPTS {
+                sleep((&pause).into()).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_in_result, clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use std::{error::Error, net::SocketAddr, num::NonZeroUsize, time::Duration};
+
+    use axum::{routing::post, Router};
+    use chrono::Utc;
+    use http::StatusCode;
+    use serde_json::{json, Value as JsonValue};
+    use tokio::{
+        sync::mpsc,
+        time::{sleep, timeout},
+    };
+
+    use super::{
+        super::{InvalidWebhook, TokenStore},
+        Webhooks,
+    };
+
+    type TestResult = Result<(), Box<dyn Error>>;
+
+    fn webhooks(allowed_hosts: &[&str]) -> Webhooks {
+        let (webhooks, dispatcher) = Webhooks::new(
+            NonZeroUsize::MIN,
+            allowed_hosts.iter().map(ToString::to_string).collect(),
+        );
+        tokio::spawn(dispatcher.run());
+
+        webhooks
+    }
+
+    /// A receiver on the loopback address, passing on the bodies `POSTed` to /hook
+    fn receiver() -> (SocketAddr, mpsc::Receiver<String>) {
+        let (received, bodies) = mpsc::channel(1);
+        let app = Router::new().route(
+            "/hook",
+            post(move |body: String| async move {
+                let _received = received.send(body).await;
+                StatusCode::OK
+            }),
+        );
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        (addr, bodies)
+    }
+
+    #[tokio::test]
+    async fn refuses_urls_that_are_not_public() {
+        let webhooks = webhooks(&[]);
+
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://172.16.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://localhost:8080/hook",
+            "http://api.LOCALHOST/hook",
+        ] {
+            assert!(
+                matches!(
+                    webhooks.parse_url(url),
+                    Err(InvalidWebhook::NotPublic { .. })
+                ),
+                "{url}"
+            );
+        }
+
+        for url in [
+            "https://example.com/hook",
+            "/hook",
+            "ftp://example.com/hook",
+        ] {
+            assert!(
+                matches!(
+                    webhooks.parse_url(url),
+                    Err(InvalidWebhook::UnsupportedScheme { .. })
+                ),
+                "{url}"
+            );
+        }
+
+        for url in ["http://203.0.113.7/hook", "http://example.com:8080/hook"] {
+            assert!(webhooks.parse_url(url).is_ok(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn allowed_hosts_may_be_local() {
+        let webhooks = webhooks(&["127.0.0.1", "Receiver.Local"]);
+
+        assert!(webhooks.parse_url("http://127.0.0.1:8080/hook").is_ok());
+        assert!(webhooks.parse_url("http://receiver.local/hook").is_ok());
+        assert!(webhooks.parse_url("http://127.0.0.2/hook").is_err());
+    }
+
+    #[tokio::test]
+    async fn posts_the_expired_token_to_an_allowed_host() -> TestResult {
+        let (addr, mut bodies) = receiver();
+        let webhooks = webhooks(&["127.0.0.1"]);
+
+        let url = webhooks.parse_url(&format!("http://{addr}/hook"))?;
+        webhooks.insert("token-1", url);
+        let meta = json!({ "user": "alice" }).as_object().cloned().unwrap();
+        webhooks.expired("token-1", &meta);
+
+        let body = timeout(Duration::from_secs(5), bodies.recv())
+            .await?
+            .ok_or("the receiver stopped")?;
+        assert_eq!(
+            serde_json::from_str::<JsonValue>(&body)?,
+            json!({ "token": "token-1", "meta": { "user": "alice" } })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn does_not_post_to_a_name_that_resolves_to_loopback() -> TestResult {
+        let (addr, mut bodies) = receiver();
+        let webhooks = webhooks(&[]);
+
+        // as if the name resolved to a public address when the token was created
+        let url = format!("http://localhost:{}/hook", addr.port()).parse()?;
+        webhooks.insert("token-1", url);
+        webhooks.expired("token-1", &serde_json::Map::new());
+
+        assert!(timeout(Duration::from_millis(500), bodies.recv())
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn the_purge_queues_the_expired_token() -> TestResult {
+        let (addr, mut bodies) = receiver();
+        let (webhooks, dispatcher) =
+            Webhooks::new(NonZeroUsize::MIN, vec![String::from("127.0.0.1")]);
+        let store = TokenStore::default().with_webhooks(Some(webhooks));
+        let url = store.parse_webhook_url(&format!("http://{addr}/hook"))?;
+
+        let meta = json!({ "user": "alice" }).as_object().cloned().unwrap();
+        let expired = store
+            .create_token_expiring_at(meta, Vec::new(), Some(url.clone()), Utc::now())?
+            .token;
+        store.create_token_full(serde_json::Map::new(), Vec::new(), Some(url))?;
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(store.remove_expired_tokens().purged, 1);
+
+        // queued by the purge, before the dispatcher runs
+        tokio::spawn(dispatcher.run());
+        let body = timeout(Duration::from_secs(5), bodies.recv())
+            .await?
+            .ok_or("the receiver stopped")?;
+        assert_eq!(
+            serde_json::from_str::<JsonValue>(&body)?,
+            json!({ "token": expired, "meta": { "user": "alice" } })
+        );
+        // not for the token that is still valid
+        assert!(timeout(Duration::from_millis(500), bodies.recv())
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -48,14 +48,14 @@ export class TokenClient {
   }
 
   public createToken(meta: Meta): FutureClientResult<string> {
-    return this.fetchText(
+    return this.fetchJson<{ token: string }>(
       TokenClient.ENDPOINT_TOKEN,
       {
         method: "POST",
         headers: CONTENT_JSON,
         body: JSON.stringify({ meta }),
       },
-    );
+    ).map((created) => created.token);
   }
 
   public shutdown(): FutureClientResult<string> {